@@ -0,0 +1,51 @@
+//! Benchmarks for drawing to the display, run with
+//! `cargo bench --no-default-features --features std --bench gpu`.
+
+use chip8::gpu::{DrawMode, Gpu};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+/// A full height sprite, as drawn by most games.
+const SPRITE: [u8; 15] = [
+    0x3C, 0x7E, 0xFF, 0xDB, 0xFF, 0x7E, 0x3C, 0x18, 0x3C, 0x7E, 0xFF, 0xDB, 0xFF, 0x7E, 0x3C,
+];
+
+fn draw(c: &mut Criterion) {
+    let mut group = c.benchmark_group("draw");
+    for &(name, mode) in &[("wrap", DrawMode::Wrap), ("clip", DrawMode::Clip)] {
+        group.bench_function(name, |b| {
+            let mut gpu = Gpu::new();
+            // across the bottom right corner, so both edges are crossed
+            b.iter(|| gpu.draw(black_box(60), black_box(25), black_box(&SPRITE), mode));
+        });
+    }
+    group.finish();
+}
+
+fn clear(c: &mut Criterion) {
+    c.bench_function("clear", |b| {
+        let mut gpu = Gpu::new();
+        b.iter(|| {
+            gpu.draw(black_box(0), black_box(0), &SPRITE, DrawMode::Wrap);
+            gpu.clear();
+        });
+    });
+}
+
+fn read_pixels(c: &mut Criterion) {
+    c.bench_function("read pixels", |b| {
+        let mut gpu = Gpu::new();
+        gpu.draw(10, 10, &SPRITE, DrawMode::Wrap);
+        b.iter(|| {
+            let mut lit = 0;
+            for y in 0..chip8::gpu::HEIGHT {
+                for x in 0..chip8::gpu::WIDTH {
+                    lit += black_box(&gpu).pixel(x, y) as u32;
+                }
+            }
+            lit
+        });
+    });
+}
+
+criterion_group!(benches, draw, clear, read_pixels);
+criterion_main!(benches);
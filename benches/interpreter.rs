@@ -0,0 +1,119 @@
+//! Benchmarks for the interpreter, run with
+//! `cargo bench --no-default-features --features std --bench interpreter`.
+
+use chip8::cpu::Cpu;
+use chip8::disasm::Instruction;
+use chip8::gpu::Gpu;
+use chip8::keypad::{Keypad, KeypadSetting};
+use chip8::roms;
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+
+/// The number of instructions each ROM is run for.
+const CYCLES: u64 = 10_000;
+
+/// The number of instructions run between each tick of the timers.
+const INSTRUCTIONS_PER_FRAME: u64 = 10;
+
+/// Returns a CPU with the given program loaded, and the default quirks
+/// so that every ROM is measured running the same instructions.
+fn cpu_with(program: &[u8]) -> Cpu {
+    let mut cpu: Cpu = Cpu::with_rng(Gpu::new(), Keypad::new(KeypadSetting::Qwerty), random_byte);
+    cpu.load_rom(program).unwrap();
+    cpu
+}
+
+/// Returns the same byte every time, so that runs are repeatable.
+fn random_byte() -> u8 {
+    0x5A
+}
+
+/// Runs the CPU for the given number of instructions.
+fn run(cpu: &mut Cpu, cycles: u64) {
+    for cycle in 0..cycles {
+        cpu.execute_cycle();
+        if cycle % INSTRUCTIONS_PER_FRAME == INSTRUCTIONS_PER_FRAME - 1 {
+            cpu.tick_timers();
+        }
+    }
+}
+
+fn roms(c: &mut Criterion) {
+    let mut group = c.benchmark_group("rom");
+    group.throughput(Throughput::Elements(CYCLES));
+    for rom in roms::ROMS {
+        group.bench_function(rom.name, |b| {
+            b.iter_batched(
+                || cpu_with(rom.data),
+                |mut cpu| run(&mut cpu, CYCLES),
+                BatchSize::SmallInput,
+            );
+        });
+    }
+    group.finish();
+}
+
+/// Benchmarks decoding every opcode, which the CPU does for each
+/// instruction before executing it.
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    group.throughput(Throughput::Elements(0x10000));
+    group.bench_function("every opcode", |b| {
+        b.iter(|| {
+            for opcode in 0..=0xFFFF {
+                black_box(Instruction::decode(black_box(opcode)));
+            }
+        });
+    });
+    group.finish();
+}
+
+/// Benchmarks a loop of the given instructions, followed by a jump back
+/// to the start.
+fn bench_loop(c: &mut Criterion, name: &str, body: &[u8]) {
+    let mut program = body.to_vec();
+    program.extend_from_slice(&[0x12, 0x00]);
+    bench_program(c, name, &program);
+}
+
+/// Benchmarks 1000 instructions of the given program, which must loop
+/// forever.
+fn bench_program(c: &mut Criterion, name: &str, program: &[u8]) {
+    let mut group = c.benchmark_group("execute");
+    group.throughput(Throughput::Elements(1000));
+    group.bench_function(name, |b| {
+        let mut cpu = cpu_with(program);
+        b.iter(|| run(&mut cpu, 1000));
+    });
+    group.finish();
+}
+
+fn alu(c: &mut Criterion) {
+    // ADD, SUB, SUBN, SHR, SHL, OR, AND, XOR between V1 and V2
+    bench_loop(
+        c,
+        "alu",
+        &[
+            0x81, 0x24, 0x81, 0x25, 0x81, 0x27, 0x81, 0x26, 0x81, 0x2E, 0x81, 0x21, 0x81, 0x22,
+            0x81, 0x23,
+        ],
+    );
+}
+
+fn draw(c: &mut Criterion) {
+    // LD I, font 0; DRW V0, V1, 5; ADD V0, 3; ADD V1, 1
+    bench_loop(c, "draw", &[0xA0, 0x00, 0xD0, 0x15, 0x70, 0x03, 0x71, 0x01]);
+}
+
+fn branches(c: &mut Criterion) {
+    // SE V0, 1; SNE V0, 1 (skips); SE V0, V1; CALL 0x20A; JP 0x200; RET
+    bench_program(
+        c,
+        "branches",
+        &[
+            0x30, 0x01, 0x40, 0x01, 0x50, 0x10, 0x22, 0x0A, 0x12, 0x00, 0x00, 0xEE,
+        ],
+    );
+}
+
+criterion_group!(benches, roms, decode, alu, draw, branches);
+criterion_main!(benches);
@@ -0,0 +1,127 @@
+//! The emulator, exposed to JavaScript through `wasm-bindgen`.
+
+use chip8::cpu::Cpu;
+use chip8::gpu::{self, Gpu};
+use chip8::keypad::{Keypad, KeypadSetting};
+use chip8::runner;
+use wasm_bindgen::prelude::*;
+
+/// The default number of instructions run per frame.
+const DEFAULT_INSTRUCTIONS_PER_FRAME: u32 = 8;
+
+/// The emulator, as exposed to JavaScript.
+///
+/// JavaScript drives the frames itself, e.g. from `requestAnimationFrame`,
+/// calling `run_frame` 60 times a second and then reading the display
+/// through `framebuffer`.
+#[wasm_bindgen]
+pub struct Emulator {
+    /// The CPU, which owns the display memory and keypad.
+    cpu: Cpu,
+
+    /// The number of instructions run per frame.
+    instructions_per_frame: u32,
+
+    /// The display, one byte per pixel, which is 1 if the pixel is set
+    /// and 0 otherwise. Rows are `width` bytes long.
+    framebuffer: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl Emulator {
+    /// Creates a new emulator with no ROM loaded.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Emulator {
+        Emulator {
+            cpu: new_cpu(),
+            instructions_per_frame: DEFAULT_INSTRUCTIONS_PER_FRAME,
+            framebuffer: vec![0; (gpu::WIDTH * gpu::HEIGHT) as usize],
+        }
+    }
+
+    /// Resets the emulator and loads the given ROM. Throws if the ROM is
+    /// too large to fit in memory, leaving the emulator as it was.
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), JsValue> {
+        let mut cpu = new_cpu();
+        cpu.load_rom(rom)
+            .map_err(|err| JsValue::from_str(&format!("Failed to load ROM {}", err)))?;
+        self.cpu = cpu;
+        self.update_framebuffer();
+        Ok(())
+    }
+
+    /// Runs a single frame, and updates the framebuffer.
+    pub fn run_frame(&mut self) {
+        runner::run_frame(&mut self.cpu, self.instructions_per_frame, &mut ());
+        self.update_framebuffer();
+    }
+
+    /// Presses the given key, from `0x0` to `0xF`. Other keys are ignored.
+    pub fn key_down(&mut self, key: u8) {
+        if key < 16 {
+            self.cpu.keypad.key_down(key);
+        }
+    }
+
+    /// Releases the given key, from `0x0` to `0xF`. Other keys are ignored.
+    pub fn key_up(&mut self, key: u8) {
+        if key < 16 {
+            self.cpu.keypad.key_up(key);
+        }
+    }
+
+    /// Sets the number of instructions run per frame.
+    pub fn set_instructions_per_frame(&mut self, instructions_per_frame: u32) {
+        self.instructions_per_frame = instructions_per_frame;
+    }
+
+    /// Returns true if the tone should be playing.
+    pub fn is_sound_playing(&self) -> bool {
+        self.cpu.is_sound_playing()
+    }
+
+    /// Returns a pointer into the WebAssembly memory to the framebuffer,
+    /// which is `width * height` bytes long.
+    pub fn framebuffer(&self) -> *const u8 {
+        self.framebuffer.as_ptr()
+    }
+
+    /// Returns the width of the display in pixels.
+    pub fn width(&self) -> u32 {
+        gpu::WIDTH
+    }
+
+    /// Returns the height of the display in pixels.
+    pub fn height(&self) -> u32 {
+        gpu::HEIGHT
+    }
+}
+
+impl Emulator {
+    /// Copies the VRAM into the framebuffer.
+    fn update_framebuffer(&mut self) {
+        if !self.cpu.gpu.is_dirty() {
+            return;
+        }
+        let graphics = &self.cpu.gpu;
+        for y in 0..gpu::HEIGHT {
+            for x in 0..gpu::WIDTH {
+                self.framebuffer[(y * gpu::WIDTH + x) as usize] = graphics.pixel(x, y) as u8;
+            }
+        }
+        self.cpu.gpu.clear_dirty();
+    }
+}
+
+impl Default for Emulator {
+    fn default() -> Self {
+        Emulator::new()
+    }
+}
+
+/// Returns a new CPU which gets random bytes from `Math.random`.
+fn new_cpu() -> Cpu {
+    Cpu::with_rng(Gpu::new(), Keypad::new(KeypadSetting::Qwerty), || {
+        (js_sys::Math::random() * 256.0) as u8
+    })
+}
@@ -0,0 +1,69 @@
+//! Tests for the JavaScript API, run under Node with
+//! `wasm-pack test --node chip8-wasm`.
+
+#![cfg(target_arch = "wasm32")]
+
+use chip8_wasm::Emulator;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+/// Reads the framebuffer through the pointer handed to JavaScript.
+fn framebuffer(emulator: &Emulator) -> Vec<u8> {
+    let len = (emulator.width() * emulator.height()) as usize;
+    unsafe { std::slice::from_raw_parts(emulator.framebuffer(), len) }.to_vec()
+}
+
+#[wasm_bindgen_test]
+fn draws_a_font_sprite_into_the_framebuffer() {
+    let mut emulator = Emulator::new();
+    // LD V0, 0; LD F, V0; DRW V0, V0, 5; JP 0x206
+    emulator
+        .load_rom(&[0x60, 0x00, 0xF0, 0x29, 0xD0, 0x05, 0x12, 0x06])
+        .unwrap();
+    emulator.run_frame();
+
+    let pixels = framebuffer(&emulator);
+    let width = emulator.width() as usize;
+    // the top row of '0' is 0xF0
+    assert_eq!(&pixels[..8], &[1, 1, 1, 1, 0, 0, 0, 0]);
+    // the second row is 0x90
+    assert_eq!(&pixels[width..width + 8], &[1, 0, 0, 1, 0, 0, 0, 0]);
+}
+
+#[wasm_bindgen_test]
+fn waits_for_a_key_press_and_release() {
+    let mut emulator = Emulator::new();
+    // LD V1, K; LD F, V1; DRW V0, V0, 5; JP 0x208
+    emulator
+        .load_rom(&[0xF1, 0x0A, 0xF1, 0x29, 0xD0, 0x05, 0x12, 0x08])
+        .unwrap();
+    emulator.run_frame();
+    assert!(framebuffer(&emulator).iter().all(|pixel| *pixel == 0));
+
+    emulator.key_down(0x1);
+    emulator.key_up(0x1);
+    emulator.run_frame();
+
+    // the top row of '1' is 0x20
+    assert_eq!(&framebuffer(&emulator)[..8], &[0, 0, 1, 0, 0, 0, 0, 0]);
+}
+
+#[wasm_bindgen_test]
+fn ignores_keys_outside_the_keypad() {
+    let mut emulator = Emulator::new();
+    emulator.key_down(0x10);
+    emulator.key_up(0xFF);
+}
+
+#[wasm_bindgen_test]
+fn rejects_roms_too_large_for_memory() {
+    let mut emulator = Emulator::new();
+    // LD V0, 0; LD F, V0; DRW V0, V0, 5; JP 0x206
+    emulator
+        .load_rom(&[0x60, 0x00, 0xF0, 0x29, 0xD0, 0x05, 0x12, 0x06])
+        .unwrap();
+    assert!(emulator.load_rom(&[0; 4000]).is_err());
+
+    // the ROM loaded before is still running
+    emulator.run_frame();
+    assert_eq!(&framebuffer(&emulator)[..8], &[1, 1, 1, 1, 0, 0, 0, 0]);
+}
@@ -0,0 +1,17 @@
+#!/bin/sh
+# Runs every check the crate should pass, including the builds for the
+# embedded and WebAssembly targets which a plain `cargo test` doesn't
+# cover. Needs the thumbv7em-none-eabihf and wasm32-unknown-unknown
+# targets installed.
+set -ex
+
+cargo fmt -- --check
+cargo clippy --workspace --all-targets -- -D warnings
+cargo test --no-default-features --features std
+cargo test -p chip8-wasm
+
+# the core must keep building without std
+cargo build --lib --no-default-features
+cargo build --lib --no-default-features --target thumbv7em-none-eabihf
+
+cargo clippy -p chip8-wasm --target wasm32-unknown-unknown --all-targets -- -D warnings
@@ -0,0 +1,143 @@
+use crate::screenshot;
+use chip8::palette::Palette;
+use chip8::persistence::Persistence;
+use chip8::quirks::Quirks;
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// Settings read from the config file.
+///
+/// The file is made up of `key = value` lines. Blank lines and lines
+/// starting with `#` are ignored. For example:
+/// ```text
+/// # amber on black
+/// palette = #000000,#FFB000
+/// ```
+#[derive(Default)]
+pub struct Config {
+    /// The palette to draw the display with.
+    pub palette: Option<Palette>,
+
+    /// How the display fades pixels which have been switched off.
+    pub persistence: Option<Persistence>,
+
+    /// The directory to save screenshots to.
+    pub screenshot_dir: Option<PathBuf>,
+
+    /// The scale to save screenshots at.
+    pub screenshot_scale: Option<u32>,
+
+    /// The interpreter behaviours to follow.
+    pub quirks: Option<Quirks>,
+}
+
+impl Config {
+    /// Returns the default location of the config file, which is
+    /// `chip8/config` in `$XDG_CONFIG_HOME`, or `~/.config` if unset.
+    pub fn default_path() -> Option<PathBuf> {
+        env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+            .map(|dir| dir.join("chip8").join("config"))
+    }
+
+    /// Loads the config file at the given path. A missing file is treated
+    /// as an empty one.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match fs::read_to_string(path) {
+            Ok(contents) => {
+                Config::parse(&contents).map_err(|err| format!("{}: {}", path.display(), err))
+            }
+            Err(ref err) if err.kind() == ErrorKind::NotFound => Ok(Config::default()),
+            Err(err) => Err(format!("{}: {}", path.display(), err)),
+        }
+    }
+
+    /// Parses the contents of a config file.
+    fn parse(contents: &str) -> Result<Self, String> {
+        let mut config = Config::default();
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut parts = line.splitn(2, '=').map(str::trim);
+            let key = parts.next().unwrap_or_default();
+            let value = parts
+                .next()
+                .ok_or_else(|| format!("line {}: expected 'key = value'", number + 1))?;
+            match key {
+                "palette" => config.palette = Some(Palette::parse(value)?),
+                "persistence" => config.persistence = Some(Persistence::parse(value)?),
+                "quirks" => config.quirks = Some(Quirks::parse(value)?),
+                "screenshot_dir" => config.screenshot_dir = Some(PathBuf::from(value)),
+                "screenshot_scale" => {
+                    config.screenshot_scale = Some(
+                        screenshot::parse_scale(value)
+                            .map_err(|_| format!("line {}: invalid scale", number + 1))?,
+                    )
+                }
+                _ => return Err(format!("line {}: unknown key '{}'", number + 1, key)),
+            }
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8::palette;
+
+    #[test]
+    fn parses_every_key() {
+        let config = Config::parse(
+            "# comment\n\
+             \n\
+             palette = amber\n\
+             persistence = decay\n\
+             quirks = vip\n\
+             screenshot_dir = shots\n\
+             screenshot_scale = 4\n",
+        )
+        .unwrap();
+        assert_eq!(config.palette, Some(palette::AMBER));
+        assert_eq!(config.persistence, Some(Persistence::Decay));
+        assert_eq!(config.quirks, Some(Quirks::COSMAC_VIP));
+        assert_eq!(config.screenshot_dir, Some(PathBuf::from("shots")));
+        assert_eq!(config.screenshot_scale, Some(4));
+    }
+
+    #[test]
+    fn reports_the_line_of_an_error() {
+        let err = |contents| Config::parse(contents).err().unwrap();
+        assert_eq!(
+            err("palette = amber\ncolour = red"),
+            "line 2: unknown key 'colour'"
+        );
+        assert_eq!(err("palette"), "line 1: expected 'key = value'");
+        assert_eq!(err("screenshot_scale = 0"), "line 1: invalid scale");
+        assert_eq!(err("screenshot_scale = 1024"), "line 1: invalid scale");
+        assert!(err("palette = sepia").contains("sepia"));
+    }
+
+    #[test]
+    fn a_missing_file_is_empty() {
+        let path = env::temp_dir().join("chip8-missing-config");
+        let config = Config::load(&path).unwrap();
+        assert!(config.palette.is_none());
+        assert!(config.screenshot_scale.is_none());
+    }
+
+    #[test]
+    fn loads_a_file() {
+        let path = env::temp_dir().join(format!("chip8-config-{}", std::process::id()));
+        fs::write(&path, "persistence = blend\n").unwrap();
+        let config = Config::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(config.unwrap().persistence, Some(Persistence::Blend));
+    }
+}
@@ -1,11 +1,36 @@
+use crate::disasm::Instruction;
 use crate::gpu::Gpu;
 use crate::keypad::Keypad;
-use rand::prelude::random;
+use crate::quirks::Quirks;
 
+use core::fmt::{self, Display, Formatter};
 use log::debug;
 
-/// Represents the CPU
-pub struct Cpu {
+/// The largest ROM which fits in memory, from `0x200` to the end.
+pub const MAX_ROM_SIZE: usize = 4096 - 0x200;
+
+/// The error returned when a ROM is too large to fit in memory.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RomTooLarge {
+    /// The length of the ROM, in bytes.
+    pub len: usize,
+}
+
+impl Display for RomTooLarge {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} bytes is larger than the {} which fit in memory",
+            self.len, MAX_ROM_SIZE
+        )
+    }
+}
+
+/// Represents the CPU.
+///
+/// `R` is the source of random bytes, which can be any closure, so that it
+/// can hold state such as a seeded generator.
+pub struct Cpu<R = fn() -> u8> {
     /// Index register
     i: u16,
 
@@ -29,12 +54,12 @@ pub struct Cpu {
 
     /// The delay timer.
     ///
-    /// Counts down one on every cycle.
+    /// Counts down one on every frame.
     dt: u8,
 
     /// The sound timer.
     ///
-    /// Counts down one on every cycle and plays a sound whilst >0.
+    /// Counts down one on every frame and plays a sound whilst >0.
     st: u8,
 
     /// The GPU
@@ -42,20 +67,44 @@ pub struct Cpu {
 
     /// The keypad
     pub keypad: Keypad,
+
+    /// The interpreter behaviours to follow.
+    pub quirks: Quirks,
+
+    /// The source of random bytes.
+    rng: R,
+
+    /// The number of instructions executed so far.
+    cycles: u64,
+
+    /// True from the start of a frame until the first instruction in it
+    /// has executed, which is when a waiting draw completes.
+    vblank: bool,
+
+    /// True if the last instruction was a draw which is waiting for the
+    /// next frame, with the display wait quirk.
+    waiting_for_vblank: bool,
 }
 
 impl Cpu {
-    /// Returns a new CPU instance
+    /// Returns a new CPU instance, which uses the thread-local random
+    /// number generator.
+    #[cfg(feature = "std")]
     pub fn new(gpu: Gpu, keypad: Keypad) -> Self {
-        // fill memory with font set
-        let mut initial_memory = [0; 4096];
-        initial_memory[..FONT_SET.len()].clone_from_slice(&FONT_SET[..]);
+        Cpu::with_rng(gpu, keypad, rand::random)
+    }
+}
 
+impl<R: FnMut() -> u8> Cpu<R> {
+    /// Returns a new CPU instance which gets random bytes from the given
+    /// closure, e.g. a seeded generator or a hardware RNG. This is the
+    /// only way to create a CPU without `std`.
+    pub fn with_rng(gpu: Gpu, keypad: Keypad, rng: R) -> Self {
         Cpu {
             i: 0,
             pc: 0x200,
 
-            memory: initial_memory,
+            memory: initial_memory(),
             v: [0; 16],
             stack: [0; 16],
             sp: 0,
@@ -63,25 +112,115 @@ impl Cpu {
             st: 0,
             keypad,
             gpu,
+            quirks: Quirks::default(),
+            rng,
+            cycles: 0,
+            vblank: false,
+            waiting_for_vblank: false,
         }
     }
 
-    /// Loads the given ROM into memory
-    pub fn load_rom(&mut self, rom: &[u8]) {
+    /// Restores the state the CPU was created in, with only the font in
+    /// memory and a blank display, ready for a ROM to be loaded again.
+    ///
+    /// The quirks, the random number generator and the keys held on the
+    /// keypad are kept.
+    pub fn reset(&mut self) {
+        self.i = 0;
+        self.pc = 0x200;
+        self.memory = initial_memory();
+        self.v = [0; 16];
+        self.stack = [0; 16];
+        self.sp = 0;
+        self.dt = 0;
+        self.st = 0;
+        self.gpu = Gpu::new();
+        self.keypad.clear_edges();
+        self.cycles = 0;
+        self.vblank = false;
+        self.waiting_for_vblank = false;
+    }
+
+    /// Loads the given ROM into memory. Nothing is loaded if it's larger
+    /// than `MAX_ROM_SIZE`.
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), RomTooLarge> {
+        if rom.len() > MAX_ROM_SIZE {
+            return Err(RomTooLarge { len: rom.len() });
+        }
+
         // ROMs are loaded into memory from 0x200
-        for x in 0..rom.len() {
-            debug!("loading byte {:X} into {:X}", rom[x], 0x200 + x);
-            self.memory[0x200 + x] = rom[x];
+        for (x, byte) in rom.iter().enumerate() {
+            debug!("loading byte {:X} into {:X}", byte, 0x200 + x);
+            self.memory[0x200 + x] = *byte;
         }
+        Ok(())
     }
 
+    /// Fetches and executes a single instruction
     pub fn execute_cycle(&mut self) {
+        let opcode = self.opcode();
+        self.waiting_for_vblank = false;
+        self.execute_opcode(opcode);
+        self.vblank = false;
+        self.cycles += 1;
+    }
+
+    /// Returns the opcode at the program counter, which is the next one
+    /// to be executed.
+    pub fn opcode(&self) -> u16 {
         // each opcode is two bytes, and so needs to be combined from
         // two successive pc locations
         let part1 = u16::from(self.memory[self.pc as usize]);
         let part2 = u16::from(self.memory[self.pc as usize + 1]);
-        let opcode = (part1 << 8) | part2;
-        self.execute_opcode(opcode);
+        (part1 << 8) | part2
+    }
+
+    /// Returns true if the CPU is stalled on a draw until the next frame,
+    /// in which case the rest of the current frame can be skipped.
+    pub fn is_waiting_for_vblank(&self) -> bool {
+        self.waiting_for_vblank
+    }
+
+    /// Returns the number of instructions executed so far.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Returns the program counter.
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    /// Returns the index register.
+    pub fn i(&self) -> u16 {
+        self.i
+    }
+
+    /// Returns the registers, V0 to VF.
+    pub fn v(&self) -> &[u8; 16] {
+        &self.v
+    }
+
+    /// Returns the stack pointer.
+    pub fn sp(&self) -> u8 {
+        self.sp
+    }
+
+    /// Returns the delay timer.
+    pub fn dt(&self) -> u8 {
+        self.dt
+    }
+
+    /// Returns the sound timer.
+    pub fn st(&self) -> u8 {
+        self.st
+    }
+
+    /// Counts the delay and sound timers down by one, and marks the start
+    /// of a new frame for draws waiting on it. Should be called once per
+    /// frame, at 60Hz.
+    pub fn tick_timers(&mut self) {
+        self.vblank = true;
         if self.dt > 0 {
             self.dt -= 1
         };
@@ -90,61 +229,54 @@ impl Cpu {
         };
     }
 
+    /// Returns true if the sound timer is running, and so the tone should
+    /// be playing.
+    pub fn is_sound_playing(&self) -> bool {
+        self.st > 0
+    }
+
     // ---------------------------------------------------------
     /// Executes the given op code.
     ///
-    /// All instructions are 2 bytes long
+    /// All instructions are 2 bytes long. They're decoded by the same
+    /// decoder the trace and profiler use, so that they all agree.
     fn execute_opcode(&mut self, opcode: u16) {
-        // split the op-code up to make the matching logic saner
-        let nibbles = (
-            ((opcode & 0xF000) >> 12) as u8,
-            ((opcode & 0x0F00) >> 8) as u8,
-            ((opcode & 0x00F0) >> 4) as u8,
-            (opcode & 0x000F) as u8,
-        );
-
-        let x = nibbles.1;
-        let y = nibbles.2;
-        let n = nibbles.3;
-        let kk = (opcode & 0x00FF) as u8;
-        let addr = (opcode & 0x0FFF) as u16;
-
-        match nibbles {
-            (0x00, 0x00, 0x0E, 0x00) => self.cls(),
-            (0x00, 0x00, 0x0E, 0x0E) => self.ret(),
-            (0x01, _, _, _) => self.jp_addr(addr),
-            (0x02, _, _, _) => self.call(addr),
-            (0x03, _, _, _) => self.se_x_kk(x, kk),
-            (0x04, _, _, _) => self.sne_x_kk(x, kk),
-            (0x05, _, _, 0x00) => self.se_x_y(x, y),
-            (0x06, _, _, _) => self.ld_x_kk(x, kk),
-            (0x07, _, _, _) => self.add_x_kk(x, kk),
-            (0x08, _, _, 0x00) => self.ld_x_y(x, y),
-            (0x08, _, _, 0x01) => self.or(x, y),
-            (0x08, _, _, 0x02) => self.and(x, y),
-            (0x08, _, _, 0x03) => self.xor(x, y),
-            (0x08, _, _, 0x04) => self.add_x_y(x, y),
-            (0x08, _, _, 0x05) => self.sub_x_y(x, y),
-            (0x08, _, _, 0x06) => self.shr(x),
-            (0x08, _, _, 0x07) => self.subn(x, y),
-            (0x08, _, _, 0x0E) => self.shl(x),
-            (0x09, _, _, 0x00) => self.sne_x_y(x, y),
-            (0x0A, _, _, _) => self.ld_i_addr(addr),
-            (0x0B, _, _, _) => self.jp_v0_addr(addr),
-            (0x0C, _, _, _) => self.rnd(x, kk),
-            (0x0D, _, _, _) => self.drw(x, y, n),
-            (0x0E, _, 0x09, 0x0E) => self.skp(x),
-            (0x0E, _, 0x0A, 0x01) => self.sknp(x),
-            (0x0F, _, 0x00, 0x07) => self.ld_get_dt(x),
-            (0x0F, _, 0x00, 0x0A) => self.ld_key(x),
-            (0x0F, _, 0x01, 0x05) => self.ld_set_dt(x),
-            (0x0F, _, 0x01, 0x08) => self.ld_set_st(x),
-            (0x0F, _, 0x01, 0x0E) => self.add_i_vx(x),
-            (0x0F, _, 0x02, 0x09) => self.ld_sprite(x),
-            (0x0F, _, 0x03, 0x03) => self.ld_bcd(x),
-            (0x0F, _, 0x05, 0x05) => self.ld_set_memory(x),
-            (0x0F, _, 0x06, 0x05) => self.ld_get_memory(x),
-            (_, _, _, _) => self.noop(),
+        match Instruction::decode(opcode) {
+            Instruction::Cls => self.cls(),
+            Instruction::Ret => self.ret(),
+            Instruction::Jp(addr) => self.jp_addr(addr),
+            Instruction::Call(addr) => self.call(addr),
+            Instruction::SeByte(x, kk) => self.se_x_kk(x, kk),
+            Instruction::SneByte(x, kk) => self.sne_x_kk(x, kk),
+            Instruction::SeReg(x, y) => self.se_x_y(x, y),
+            Instruction::LdByte(x, kk) => self.ld_x_kk(x, kk),
+            Instruction::AddByte(x, kk) => self.add_x_kk(x, kk),
+            Instruction::LdReg(x, y) => self.ld_x_y(x, y),
+            Instruction::Or(x, y) => self.or(x, y),
+            Instruction::And(x, y) => self.and(x, y),
+            Instruction::Xor(x, y) => self.xor(x, y),
+            Instruction::AddReg(x, y) => self.add_x_y(x, y),
+            Instruction::Sub(x, y) => self.sub_x_y(x, y),
+            Instruction::Shr(x, y) => self.shr(x, y),
+            Instruction::Subn(x, y) => self.subn(x, y),
+            Instruction::Shl(x, y) => self.shl(x, y),
+            Instruction::SneReg(x, y) => self.sne_x_y(x, y),
+            Instruction::LdI(addr) => self.ld_i_addr(addr),
+            Instruction::JpV0(addr) => self.jp_v0_addr(addr),
+            Instruction::Rnd(x, kk) => self.rnd(x, kk),
+            Instruction::Drw(x, y, n) => self.drw(x, y, n),
+            Instruction::Skp(x) => self.skp(x),
+            Instruction::Sknp(x) => self.sknp(x),
+            Instruction::LdGetDt(x) => self.ld_get_dt(x),
+            Instruction::LdKey(x) => self.ld_key(x),
+            Instruction::LdSetDt(x) => self.ld_set_dt(x),
+            Instruction::LdSetSt(x) => self.ld_set_st(x),
+            Instruction::AddI(x) => self.add_i_vx(x),
+            Instruction::LdSprite(x) => self.ld_sprite(x),
+            Instruction::LdBcd(x) => self.ld_bcd(x),
+            Instruction::LdSetMemory(x) => self.ld_set_memory(x),
+            Instruction::LdGetMemory(x) => self.ld_get_memory(x),
+            Instruction::Unknown(_) => self.noop(),
         }
     }
 
@@ -214,70 +346,84 @@ impl Cpu {
     /// Set Vx = Vx | Vy
     fn or(&mut self, x: u8, y: u8) {
         self.v[x as usize] |= self.v[y as usize];
+        self.reset_vf();
         self.pc += 2;
     }
 
     /// Set Vx = Vx & Vy
     fn and(&mut self, x: u8, y: u8) {
         self.v[x as usize] &= self.v[y as usize];
+        self.reset_vf();
         self.pc += 2;
     }
 
     /// Set Vx = Vx ^ Vy
     fn xor(&mut self, x: u8, y: u8) {
         self.v[x as usize] ^= self.v[y as usize];
+        self.reset_vf();
         self.pc += 2;
     }
 
     /// Set Vx = Vx + Vy, and set Vf = carry
     /// Only the lowest 8 bits of the result are set to Vx
     fn add_x_y(&mut self, x: u8, y: u8) {
-        let vx = u16::from(self.v[x as usize]);
-        let vy = u16::from(self.v[y as usize]);
-        let result = vx + vy;
-        self.v[0xF] = if result > 0xFF { 1 } else { 0 };
-        self.v[x as usize] = result as u8;
-        self.pc += 2;
+        let (result, carry) = self.v[x as usize].overflowing_add(self.v[y as usize]);
+        self.set_with_flag(x, result, carry);
     }
 
-    /// Set Vx = Vx - Vy, and set Vy = NOT borrow
-    /// If Vx > Vy, Vf is set to 1
+    /// Set Vx = Vx - Vy, and set Vf = NOT borrow
+    /// If Vx >= Vy, Vf is set to 1
     fn sub_x_y(&mut self, x: u8, y: u8) {
-        self.v[0xF] = if self.v[x as usize] > self.v[y as usize] {
-            1
-        } else {
-            0
-        };
-        self.v[x as usize] = self.v[x as usize].wrapping_sub(self.v[y as usize]);
-        self.pc += 2;
+        let (result, borrow) = self.v[x as usize].overflowing_sub(self.v[y as usize]);
+        self.set_with_flag(x, result, !borrow);
     }
 
-    /// Set Vx = Vx SHR 1
-    /// If the least-significant bit of Vx == 1, Vf is set to 1
-    fn shr(&mut self, x: u8) {
-        self.v[0xF] = self.v[x as usize] & 1;
-        self.v[x as usize] >>= 1;
-        self.pc += 2;
+    /// Set Vx = Vx SHR 1, or Vy SHR 1 with the shift quirk
+    /// If the least-significant bit shifted is 1, Vf is set to 1
+    fn shr(&mut self, x: u8, y: u8) {
+        let value = self.shift_operand(x, y);
+        self.set_with_flag(x, value >> 1, value & 1 == 1);
     }
 
     /// Set Vx = Vy - Vx, set Vf = NOT borrow
+    /// If Vy >= Vx, Vf is set to 1
     fn subn(&mut self, x: u8, y: u8) {
-        self.v[0xF] = if self.v[y as usize] > self.v[x as usize] {
-            1
-        } else {
-            0
-        };
-        self.v[x as usize] = self.v[y as usize].wrapping_sub(self.v[x as usize]);
-        self.pc += 2;
+        let (result, borrow) = self.v[y as usize].overflowing_sub(self.v[x as usize]);
+        self.set_with_flag(x, result, !borrow);
     }
 
-    /// Set Vx = Vx SHL 1
-    fn shl(&mut self, x: u8) {
-        self.v[0xF] = (self.v[x as usize] & 0b1000_0000) >> 7;
-        self.v[x as usize] <<= 1;
+    /// Set Vx = Vx SHL 1, or Vy SHL 1 with the shift quirk
+    /// If the most-significant bit shifted is 1, Vf is set to 1
+    fn shl(&mut self, x: u8, y: u8) {
+        let value = self.shift_operand(x, y);
+        self.set_with_flag(x, value << 1, value & 0b1000_0000 != 0);
+    }
+
+    /// Sets Vx to the result of an arithmetic instruction, and Vf to its
+    /// flag. The operands must already have been read, as either may be
+    /// Vf. The flag is written last, so it wins when Vx is Vf.
+    fn set_with_flag(&mut self, x: u8, result: u8, flag: bool) {
+        self.v[x as usize] = result;
+        self.v[0xF] = flag as u8;
         self.pc += 2;
     }
 
+    /// Returns the register shifted by `8xy6` and `8xyE`.
+    fn shift_operand(&self, x: u8, y: u8) -> u8 {
+        if self.quirks.shift_uses_vy {
+            self.v[y as usize]
+        } else {
+            self.v[x as usize]
+        }
+    }
+
+    /// Resets Vf after a logical instruction, with the VF reset quirk.
+    fn reset_vf(&mut self) {
+        if self.quirks.vf_reset {
+            self.v[0xF] = 0;
+        }
+    }
+
     /// Skip the next instruction if Vx != Vy
     fn sne_x_y(&mut self, x: u8, y: u8) {
         self.pc += if self.v[x as usize] != self.v[y as usize] {
@@ -300,20 +446,30 @@ impl Cpu {
 
     /// Set Vx = random byte & kk
     fn rnd(&mut self, x: u8, kk: u8) {
-        let rand: u8 = random();
+        let rand = (self.rng)();
         self.v[x as usize] = rand & kk;
         self.pc += 2;
     }
 
     /// Display n-byte sprite starting at memory location I at (Vx, Vy).
     /// Set Vf = 1 if any pixels were erased.
+    /// With the display wait quirk, this waits for the start of the next
+    /// frame first, leaving pc on this instruction until then.
     fn drw(&mut self, x: u8, y: u8, n: u8) {
+        if self.quirks.display_wait && !self.vblank {
+            self.waiting_for_vblank = true;
+            return;
+        }
+
         let vx = self.v[x as usize];
         let vy = self.v[y as usize];
-        let bytes = (0..n as usize)
-            .map(|i| self.memory[self.i as usize + i])
-            .collect::<Vec<u8>>();
-        let collision = self.gpu.draw(vx, vy, bytes);
+        let start = self.i as usize;
+        let collision = self.gpu.draw(
+            vx,
+            vy,
+            &self.memory[start..start + n as usize],
+            self.quirks.draw_mode,
+        );
         self.v[0xF] = if collision { 1 } else { 0 };
         self.pc += 2;
     }
@@ -342,11 +498,11 @@ impl Cpu {
         self.pc += 2;
     }
 
-    /// Wait for a key press, store the value of the key in Vx
+    /// Wait for a key press and release, store the value of the key in Vx
     fn ld_key(&mut self, x: u8) {
-        if let Some(key) = self.keypad.keys.iter().position(|key| *key) {
+        if let Some(key) = self.keypad.take_released_key() {
             self.pc += 2;
-            self.v[x as usize] = key as u8;
+            self.v[x as usize] = key;
         }
         // Spin otherwise, don't increment pc
     }
@@ -415,11 +571,11 @@ impl Cpu {
 ///
 /// Each letter is represented by 5 bytes (or 8x5 pixels) of pixels
 /// set. For example, the representation for '5' is:
-/// ```
+/// ```text
 ///   0xF0, 0x80, 0xF0, 0x10, 0xF0
 /// ```
 /// The binary representation of these values makes up the letter:
-/// ```
+/// ```text
 /// Hex   Bin        Bin 1s
 /// 0xF0  1111 0000  ****
 /// 0x80  1000 0000  *
@@ -446,3 +602,689 @@ pub const FONT_SET: [u8; 80] = [
     0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
+
+/// Returns the memory of a new CPU, which holds just the font set.
+fn initial_memory() -> [u8; 4096] {
+    let mut memory = [0; 4096];
+    memory[..FONT_SET.len()].clone_from_slice(&FONT_SET[..]);
+    memory
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu::{self, DrawMode};
+    use crate::keypad::KeypadSetting;
+
+    /// The byte every test CPU's random number generator returns.
+    const RANDOM_BYTE: u8 = 0b1010_0101;
+
+    /// Returns `RANDOM_BYTE`.
+    fn random_byte() -> u8 {
+        RANDOM_BYTE
+    }
+
+    /// Builds a CPU in a given state.
+    #[derive(Clone)]
+    struct CpuBuilder {
+        i: u16,
+        pc: u16,
+        v: [u8; 16],
+        stack: [u16; 16],
+        sp: u8,
+        dt: u8,
+        st: u8,
+        memory: Vec<(u16, Vec<u8>)>,
+        pixels: Vec<(u8, u8)>,
+        keys: Vec<u8>,
+        quirks: Quirks,
+    }
+
+    impl CpuBuilder {
+        fn new() -> Self {
+            CpuBuilder {
+                i: 0,
+                pc: 0x200,
+                v: [0; 16],
+                stack: [0; 16],
+                sp: 0,
+                dt: 0,
+                st: 0,
+                memory: Vec::new(),
+                pixels: Vec::new(),
+                keys: Vec::new(),
+                quirks: Quirks::default(),
+            }
+        }
+
+        fn i(mut self, i: u16) -> Self {
+            self.i = i;
+            self
+        }
+
+        fn pc(mut self, pc: u16) -> Self {
+            self.pc = pc;
+            self
+        }
+
+        fn v(mut self, x: usize, value: u8) -> Self {
+            self.v[x] = value;
+            self
+        }
+
+        fn stack(mut self, stack: &[u16]) -> Self {
+            self.stack = [0; 16];
+            self.stack[..stack.len()].copy_from_slice(stack);
+            self.sp = stack.len() as u8;
+            self
+        }
+
+        fn sp(mut self, sp: u8) -> Self {
+            self.sp = sp;
+            self
+        }
+
+        fn dt(mut self, dt: u8) -> Self {
+            self.dt = dt;
+            self
+        }
+
+        fn st(mut self, st: u8) -> Self {
+            self.st = st;
+            self
+        }
+
+        fn memory(mut self, address: u16, bytes: &[u8]) -> Self {
+            self.memory.push((address, bytes.to_vec()));
+            self
+        }
+
+        fn pixel(mut self, x: u8, y: u8) -> Self {
+            self.pixels.push((x, y));
+            self
+        }
+
+        fn key(mut self, key: u8) -> Self {
+            self.keys.push(key);
+            self
+        }
+
+        fn quirks(mut self, quirks: Quirks) -> Self {
+            self.quirks = quirks;
+            self
+        }
+
+        fn build(&self) -> Cpu {
+            let mut cpu: Cpu =
+                Cpu::with_rng(Gpu::new(), Keypad::new(KeypadSetting::Qwerty), random_byte);
+            cpu.i = self.i;
+            cpu.pc = self.pc;
+            cpu.v = self.v;
+            cpu.stack = self.stack;
+            cpu.sp = self.sp;
+            cpu.dt = self.dt;
+            cpu.st = self.st;
+            cpu.quirks = self.quirks;
+            for (address, bytes) in &self.memory {
+                let start = *address as usize;
+                cpu.memory[start..start + bytes.len()].copy_from_slice(bytes);
+            }
+            for &(x, y) in &self.pixels {
+                cpu.gpu.draw(x, y, &[0b1000_0000], DrawMode::Wrap);
+            }
+            for &key in &self.keys {
+                cpu.keypad.key_down(key);
+            }
+            cpu
+        }
+    }
+
+    /// Everything about a CPU a single instruction can change.
+    #[derive(Debug, PartialEq)]
+    struct State {
+        i: u16,
+        pc: u16,
+        v: [u8; 16],
+        stack: [u16; 16],
+        sp: u8,
+        dt: u8,
+        st: u8,
+        memory: Vec<u8>,
+        display: Vec<u64>,
+    }
+
+    impl State {
+        fn of(cpu: &Cpu) -> Self {
+            State {
+                i: cpu.i,
+                pc: cpu.pc,
+                v: cpu.v,
+                stack: cpu.stack,
+                sp: cpu.sp,
+                dt: cpu.dt,
+                st: cpu.st,
+                memory: cpu.memory.to_vec(),
+                display: (0..gpu::HEIGHT).map(|y| cpu.gpu.row(y)).collect(),
+            }
+        }
+    }
+
+    /// Executes the opcode on a CPU built from `before`, and checks its
+    /// whole state against a CPU built from `after`.
+    fn assert_opcode(opcode: u16, before: &CpuBuilder, after: &CpuBuilder) {
+        let mut cpu = before.build();
+        cpu.execute_opcode(opcode);
+        assert_eq!(State::of(&cpu), State::of(&after.build()), "{:04X}", opcode);
+    }
+
+    #[test]
+    fn load_rom_fills_memory_up_to_the_end() {
+        let mut cpu = CpuBuilder::new().build();
+        assert_eq!(cpu.load_rom(&[0xAB; MAX_ROM_SIZE]), Ok(()));
+        assert_eq!(cpu.memory[0x200], 0xAB);
+        assert_eq!(cpu.memory[4095], 0xAB);
+    }
+
+    #[test]
+    fn load_rom_rejects_roms_too_large_for_memory() {
+        let mut cpu = CpuBuilder::new().build();
+        let rom = [0xAB; MAX_ROM_SIZE + 1];
+        assert_eq!(cpu.load_rom(&rom), Err(RomTooLarge { len: rom.len() }));
+        assert_eq!(State::of(&cpu), State::of(&CpuBuilder::new().build()));
+    }
+
+    #[test]
+    fn reset_restores_the_initial_state() {
+        let before = CpuBuilder::new()
+            .i(0x300)
+            .pc(0x280)
+            .v(0x3, 0x42)
+            .stack(&[0x202, 0x250])
+            .dt(10)
+            .st(20)
+            .memory(0x000, &[0xFF])
+            .memory(0x200, &[0x12, 0x34])
+            .pixel(10, 10)
+            .key(0x5)
+            .quirks(Quirks::COSMAC_VIP);
+        let mut cpu = before.build();
+        cpu.execute_cycle();
+        cpu.reset();
+
+        assert_eq!(State::of(&cpu), State::of(&CpuBuilder::new().build()));
+        assert_eq!(cpu.cycles(), 0);
+        assert!(cpu.gpu.is_dirty());
+        assert_eq!(cpu.quirks, Quirks::COSMAC_VIP);
+        assert!(cpu.keypad.is_key_pressed(0x5));
+    }
+
+    #[test]
+    fn cls_clears_the_display() {
+        let before = CpuBuilder::new().pixel(0, 0).pixel(63, 31);
+        assert_opcode(0x00E0, &before, &CpuBuilder::new().pc(0x202));
+    }
+
+    #[test]
+    fn ret_pops_the_stack() {
+        let before = CpuBuilder::new().pc(0x300).stack(&[0x202, 0x250]);
+        // the popped entry is left behind on the stack
+        let after = CpuBuilder::new().pc(0x250).stack(&[0x202, 0x250]).sp(1);
+        assert_opcode(0x00EE, &before, &after);
+    }
+
+    #[test]
+    fn jp_jumps() {
+        assert_opcode(0x1ABC, &CpuBuilder::new(), &CpuBuilder::new().pc(0xABC));
+    }
+
+    #[test]
+    fn call_pushes_the_return_address() {
+        let before = CpuBuilder::new().pc(0x210);
+        let after = CpuBuilder::new().pc(0x400).stack(&[0x212]);
+        assert_opcode(0x2400, &before, &after);
+    }
+
+    #[test]
+    fn se_byte_skips_when_equal() {
+        let before = CpuBuilder::new().v(3, 0x42);
+        assert_opcode(0x3342, &before, &before.clone().pc(0x204));
+        assert_opcode(0x3343, &before, &before.clone().pc(0x202));
+    }
+
+    #[test]
+    fn sne_byte_skips_when_not_equal() {
+        let before = CpuBuilder::new().v(3, 0x42);
+        assert_opcode(0x4342, &before, &before.clone().pc(0x202));
+        assert_opcode(0x4343, &before, &before.clone().pc(0x204));
+    }
+
+    #[test]
+    fn se_reg_skips_when_equal() {
+        let equal = CpuBuilder::new().v(1, 7).v(2, 7);
+        assert_opcode(0x5120, &equal, &equal.clone().pc(0x204));
+        let different = CpuBuilder::new().v(1, 7).v(2, 8);
+        assert_opcode(0x5120, &different, &different.clone().pc(0x202));
+    }
+
+    #[test]
+    fn sne_reg_skips_when_not_equal() {
+        let equal = CpuBuilder::new().v(1, 7).v(2, 7);
+        assert_opcode(0x9120, &equal, &equal.clone().pc(0x202));
+        let different = CpuBuilder::new().v(1, 7).v(2, 8);
+        assert_opcode(0x9120, &different, &different.clone().pc(0x204));
+    }
+
+    #[test]
+    fn ld_byte_sets_vx() {
+        let after = CpuBuilder::new().v(0xA, 0x5C).pc(0x202);
+        assert_opcode(0x6A5C, &CpuBuilder::new(), &after);
+    }
+
+    #[test]
+    fn add_byte_wraps_without_touching_vf() {
+        let before = CpuBuilder::new().v(2, 0xF0).v(0xF, 0x33);
+        let after = before.clone().v(2, 0x10).pc(0x202);
+        assert_opcode(0x7220, &before, &after);
+    }
+
+    #[test]
+    fn ld_reg_copies_vy() {
+        let before = CpuBuilder::new().v(4, 0x99);
+        assert_opcode(0x8340, &before, &before.clone().v(3, 0x99).pc(0x202));
+    }
+
+    #[test]
+    fn bitwise_operations() {
+        let before = CpuBuilder::new().v(1, 0b1100).v(2, 0b1010);
+        let or = before.clone().v(1, 0b1110).pc(0x202);
+        assert_opcode(0x8121, &before, &or);
+        let and = before.clone().v(1, 0b1000).pc(0x202);
+        assert_opcode(0x8122, &before, &and);
+        let xor = before.clone().v(1, 0b0110).pc(0x202);
+        assert_opcode(0x8123, &before, &xor);
+    }
+
+    #[test]
+    fn add_reg_sets_carry() {
+        let before = CpuBuilder::new().v(1, 0xFF).v(2, 0x02);
+        let after = before.clone().v(1, 0x01).v(0xF, 1).pc(0x202);
+        assert_opcode(0x8124, &before, &after);
+
+        let before = CpuBuilder::new().v(1, 0x10).v(2, 0x20).v(0xF, 1);
+        let after = before.clone().v(1, 0x30).v(0xF, 0).pc(0x202);
+        assert_opcode(0x8124, &before, &after);
+    }
+
+    #[test]
+    fn sub_sets_not_borrow() {
+        let before = CpuBuilder::new().v(1, 0x30).v(2, 0x10);
+        let after = before.clone().v(1, 0x20).v(0xF, 1).pc(0x202);
+        assert_opcode(0x8125, &before, &after);
+
+        let before = CpuBuilder::new().v(1, 0x10).v(2, 0x30).v(0xF, 1);
+        let after = before.clone().v(1, 0xE0).v(0xF, 0).pc(0x202);
+        assert_opcode(0x8125, &before, &after);
+    }
+
+    #[test]
+    fn sub_of_equal_operands_does_not_borrow() {
+        let before = CpuBuilder::new().v(1, 0x42).v(2, 0x42);
+        let after = before.clone().v(1, 0).v(0xF, 1).pc(0x202);
+        assert_opcode(0x8125, &before, &after);
+    }
+
+    #[test]
+    fn subn_sets_not_borrow() {
+        let before = CpuBuilder::new().v(1, 0x10).v(2, 0x30);
+        let after = before.clone().v(1, 0x20).v(0xF, 1).pc(0x202);
+        assert_opcode(0x8127, &before, &after);
+
+        let before = CpuBuilder::new().v(1, 0x30).v(2, 0x10).v(0xF, 1);
+        let after = before.clone().v(1, 0xE0).v(0xF, 0).pc(0x202);
+        assert_opcode(0x8127, &before, &after);
+    }
+
+    #[test]
+    fn subn_of_equal_operands_does_not_borrow() {
+        let before = CpuBuilder::new().v(1, 0x42).v(2, 0x42);
+        let after = before.clone().v(1, 0).v(0xF, 1).pc(0x202);
+        assert_opcode(0x8127, &before, &after);
+    }
+
+    #[test]
+    fn shr_shifts_out_the_lowest_bit() {
+        let before = CpuBuilder::new().v(1, 0b0000_0101);
+        let after = before.clone().v(1, 0b0000_0010).v(0xF, 1).pc(0x202);
+        assert_opcode(0x8106, &before, &after);
+
+        let before = CpuBuilder::new().v(1, 0b0000_0100).v(0xF, 1);
+        let after = before.clone().v(1, 0b0000_0010).v(0xF, 0).pc(0x202);
+        assert_opcode(0x8106, &before, &after);
+    }
+
+    #[test]
+    fn shl_shifts_out_the_highest_bit() {
+        let before = CpuBuilder::new().v(1, 0b1000_0001);
+        let after = before.clone().v(1, 0b0000_0010).v(0xF, 1).pc(0x202);
+        assert_opcode(0x810E, &before, &after);
+
+        let before = CpuBuilder::new().v(1, 0b0100_0000).v(0xF, 1);
+        let after = before.clone().v(1, 0b1000_0000).v(0xF, 0).pc(0x202);
+        assert_opcode(0x810E, &before, &after);
+    }
+
+    #[test]
+    fn alu_flags_win_when_vf_is_the_destination() {
+        // the result is written first, and then overwritten by the flag
+        let before = CpuBuilder::new().v(0xF, 0xFF).v(1, 0x02);
+        assert_opcode(0x8F14, &before, &before.clone().v(0xF, 1).pc(0x202));
+
+        let before = CpuBuilder::new().v(0xF, 0x30).v(1, 0x10);
+        assert_opcode(0x8F15, &before, &before.clone().v(0xF, 1).pc(0x202));
+
+        let before = CpuBuilder::new().v(0xF, 0x10).v(1, 0x30);
+        assert_opcode(0x8F17, &before, &before.clone().v(0xF, 1).pc(0x202));
+
+        let before = CpuBuilder::new().v(0xF, 0b0000_0010);
+        assert_opcode(0x8F06, &before, &before.clone().v(0xF, 0).pc(0x202));
+
+        let before = CpuBuilder::new().v(0xF, 0b1000_0000);
+        assert_opcode(0x8F0E, &before, &before.clone().v(0xF, 1).pc(0x202));
+    }
+
+    #[test]
+    fn alu_reads_vf_before_setting_the_flag() {
+        // VF as the source operand is read before the flag replaces it
+        let before = CpuBuilder::new().v(1, 0x30).v(0xF, 0x10);
+        let after = before.clone().v(1, 0x20).v(0xF, 1).pc(0x202);
+        assert_opcode(0x81F5, &before, &after);
+
+        let before = CpuBuilder::new().v(1, 0x10).v(0xF, 0x30);
+        let after = before.clone().v(1, 0x20).v(0xF, 1).pc(0x202);
+        assert_opcode(0x81F7, &before, &after);
+    }
+
+    #[test]
+    fn add_reg_reads_vf_before_setting_the_flag() {
+        let before = CpuBuilder::new().v(1, 0xFF).v(0xF, 0x02);
+        let after = before.clone().v(1, 0x01).v(0xF, 1).pc(0x202);
+        assert_opcode(0x81F4, &before, &after);
+    }
+
+    /// Works out Vx and VF after the ALU instruction `8xyn`, written
+    /// independently of the CPU from the arithmetic in the spec.
+    fn reference_alu(n: u8, vx: u8, vy: u8, quirks: Quirks) -> Option<(u8, Option<u8>)> {
+        let (vx, vy) = (u16::from(vx), u16::from(vy));
+        let shifted = if quirks.shift_uses_vy { vy } else { vx };
+        let logical_vf = if quirks.vf_reset { Some(0) } else { None };
+        let (result, vf) = match n {
+            0x0 => (vy, None),
+            0x1 => (vx | vy, logical_vf),
+            0x2 => (vx & vy, logical_vf),
+            0x3 => (vx ^ vy, logical_vf),
+            0x4 => (vx + vy, Some((vx + vy > 255) as u8)),
+            0x5 => (vx + 256 - vy, Some((vx >= vy) as u8)),
+            0x6 => (shifted / 2, Some((shifted % 2) as u8)),
+            0x7 => (vy + 256 - vx, Some((vy >= vx) as u8)),
+            0xE => (shifted * 2, Some((shifted / 128) as u8)),
+            _ => return None,
+        };
+        Some(((result % 256) as u8, vf))
+    }
+
+    #[test]
+    fn alu_matches_the_reference_for_every_operand_pair() {
+        for &quirks in &[Quirks::default(), Quirks::COSMAC_VIP] {
+            let mut cpu = CpuBuilder::new().quirks(quirks).build();
+            for n in 0x0..=0xF {
+                for vx in 0..=255 {
+                    for vy in 0..=255 {
+                        let (result, vf) = match reference_alu(n, vx, vy, quirks) {
+                            Some(expected) => expected,
+                            None => continue,
+                        };
+                        cpu.pc = 0x200;
+                        cpu.v[1] = vx;
+                        cpu.v[2] = vy;
+                        cpu.v[0xF] = 0xAA;
+                        cpu.execute_opcode(0x8120 | u16::from(n));
+
+                        assert_eq!(
+                            (cpu.v[1], cpu.v[2], cpu.v[0xF], cpu.pc),
+                            (result, vy, vf.unwrap_or(0xAA), 0x202),
+                            "8xy{:X} with Vx = {:02X}, Vy = {:02X} and {:?}",
+                            n,
+                            vx,
+                            vy,
+                            quirks
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn logical_operations_leave_vf_by_default() {
+        let before = CpuBuilder::new().v(1, 0b1100).v(2, 0b1010).v(0xF, 5);
+        assert_opcode(0x8121, &before, &before.clone().v(1, 0b1110).pc(0x202));
+    }
+
+    #[test]
+    fn logical_operations_reset_vf_with_the_quirk() {
+        let quirks = Quirks {
+            vf_reset: true,
+            ..Quirks::default()
+        };
+        let before = CpuBuilder::new()
+            .v(1, 0b1100)
+            .v(2, 0b1010)
+            .v(0xF, 5)
+            .quirks(quirks);
+        let or = before.clone().v(1, 0b1110).v(0xF, 0).pc(0x202);
+        assert_opcode(0x8121, &before, &or);
+        let and = before.clone().v(1, 0b1000).v(0xF, 0).pc(0x202);
+        assert_opcode(0x8122, &before, &and);
+        let xor = before.clone().v(1, 0b0110).v(0xF, 0).pc(0x202);
+        assert_opcode(0x8123, &before, &xor);
+
+        // the reset wins over the result when Vx is VF
+        assert_opcode(0x8F21, &before, &before.clone().v(0xF, 0).pc(0x202));
+    }
+
+    #[test]
+    fn shifts_use_vy_with_the_quirk() {
+        let quirks = Quirks {
+            shift_uses_vy: true,
+            ..Quirks::default()
+        };
+        let before = CpuBuilder::new()
+            .v(1, 0xFF)
+            .v(2, 0b1000_0011)
+            .quirks(quirks);
+        let shr = before.clone().v(1, 0b0100_0001).v(0xF, 1).pc(0x202);
+        assert_opcode(0x8126, &before, &shr);
+        let shl = before.clone().v(1, 0b0000_0110).v(0xF, 1).pc(0x202);
+        assert_opcode(0x812E, &before, &shl);
+
+        // Vy is read before the flag replaces it
+        let before = CpuBuilder::new().v(0xF, 0b0000_0010).quirks(quirks);
+        let after = before.clone().v(1, 0b0000_0001).v(0xF, 0).pc(0x202);
+        assert_opcode(0x81F6, &before, &after);
+    }
+
+    #[test]
+    fn ld_i_sets_i() {
+        assert_opcode(
+            0xA123,
+            &CpuBuilder::new(),
+            &CpuBuilder::new().i(0x123).pc(0x202),
+        );
+    }
+
+    #[test]
+    fn jp_v0_jumps_relative_to_v0() {
+        let before = CpuBuilder::new().v(0, 0x10);
+        assert_opcode(0xB300, &before, &before.clone().pc(0x310));
+    }
+
+    #[test]
+    fn rnd_masks_the_random_byte() {
+        let after = CpuBuilder::new().v(5, RANDOM_BYTE & 0x0F).pc(0x202);
+        assert_opcode(0xC50F, &CpuBuilder::new(), &after);
+    }
+
+    #[test]
+    fn rnd_draws_from_a_generator_with_state() {
+        let mut seed = 1u8;
+        let next = move || {
+            seed = seed.wrapping_mul(5).wrapping_add(3);
+            seed
+        };
+
+        // RND V0, FF; RND V1, FF
+        let mut cpu = Cpu::with_rng(Gpu::new(), Keypad::new(KeypadSetting::Qwerty), next);
+        cpu.load_rom(&[0xC0, 0xFF, 0xC1, 0xFF]).unwrap();
+        cpu.execute_cycle();
+        cpu.execute_cycle();
+        assert_eq!(cpu.v()[..2], [8, 43]);
+    }
+
+    #[test]
+    fn drw_draws_and_reports_collisions() {
+        // a single pixel at the top left of a sprite at (2, 3)
+        let before = CpuBuilder::new()
+            .v(0, 2)
+            .v(1, 3)
+            .i(0x300)
+            .memory(0x300, &[0x80]);
+        let after = before.clone().pixel(2, 3).pc(0x202);
+        assert_opcode(0xD011, &before, &after);
+
+        // drawing it again erases it
+        let before = before.pixel(2, 3);
+        let mut after = before.clone().v(0xF, 1).pc(0x202);
+        after.pixels.clear();
+        assert_opcode(0xD011, &before, &after);
+    }
+
+    #[test]
+    fn drw_waits_for_the_next_frame_with_the_display_wait_quirk() {
+        let quirks = Quirks {
+            display_wait: true,
+            ..Quirks::default()
+        };
+        let mut cpu = CpuBuilder::new()
+            .i(0x300)
+            .memory(0x200, &[0xD0, 0x11, 0xD0, 0x11])
+            .memory(0x300, &[0x80])
+            .quirks(quirks)
+            .build();
+
+        cpu.execute_cycle();
+        assert!(cpu.is_waiting_for_vblank());
+        assert_eq!((cpu.pc, cpu.gpu.pixel(0, 0)), (0x200, false));
+
+        // the draw completes as the first instruction of the next frame
+        cpu.tick_timers();
+        cpu.execute_cycle();
+        assert!(!cpu.is_waiting_for_vblank());
+        assert_eq!((cpu.pc, cpu.gpu.pixel(0, 0)), (0x202, true));
+
+        // and the draw after it waits for the frame after that
+        cpu.execute_cycle();
+        assert!(cpu.is_waiting_for_vblank());
+        assert_eq!((cpu.pc, cpu.gpu.pixel(0, 0)), (0x202, true));
+    }
+
+    #[test]
+    fn skp_and_sknp_check_the_key_in_vx() {
+        let pressed = CpuBuilder::new().v(2, 0xA).key(0xA);
+        assert_opcode(0xE29E, &pressed, &pressed.clone().pc(0x204));
+        assert_opcode(0xE2A1, &pressed, &pressed.clone().pc(0x202));
+
+        let released = CpuBuilder::new().v(2, 0xA).key(0xB);
+        assert_opcode(0xE29E, &released, &released.clone().pc(0x202));
+        assert_opcode(0xE2A1, &released, &released.clone().pc(0x204));
+    }
+
+    #[test]
+    fn ld_get_dt_reads_the_delay_timer() {
+        let before = CpuBuilder::new().dt(0x3C);
+        assert_opcode(0xF407, &before, &before.clone().v(4, 0x3C).pc(0x202));
+    }
+
+    #[test]
+    fn ld_key_waits_for_a_release() {
+        let mut cpu = CpuBuilder::new().build();
+        cpu.execute_opcode(0xF30A);
+        assert_eq!(cpu.pc, 0x200);
+
+        // holding a key isn't enough
+        cpu.keypad.key_down(0x7);
+        cpu.execute_opcode(0xF30A);
+        assert_eq!(cpu.pc, 0x200);
+
+        cpu.keypad.key_up(0x7);
+        cpu.execute_opcode(0xF30A);
+        assert_eq!((cpu.pc, cpu.v[3]), (0x202, 0x7));
+
+        // the release is only counted once
+        cpu.execute_opcode(0xF30A);
+        assert_eq!(cpu.pc, 0x202);
+    }
+
+    #[test]
+    fn ld_set_timers() {
+        let before = CpuBuilder::new().v(6, 0x20);
+        assert_opcode(0xF615, &before, &before.clone().dt(0x20).pc(0x202));
+        assert_opcode(0xF618, &before, &before.clone().st(0x20).pc(0x202));
+    }
+
+    #[test]
+    fn add_i_adds_vx() {
+        let before = CpuBuilder::new().i(0x100).v(1, 0x20);
+        let after = before.clone().i(0x120).pc(0x202);
+        assert_opcode(0xF11E, &before, &after);
+    }
+
+    #[test]
+    fn add_i_sets_vf_past_the_end_of_memory() {
+        let before = CpuBuilder::new().i(0xFFF).v(1, 0x01);
+        let after = before.clone().i(0x1000).v(0xF, 1).pc(0x202);
+        assert_opcode(0xF11E, &before, &after);
+    }
+
+    #[test]
+    fn ld_sprite_points_i_at_the_font() {
+        let before = CpuBuilder::new().v(2, 0xB);
+        assert_opcode(0xF229, &before, &before.clone().i(0xB * 5).pc(0x202));
+    }
+
+    #[test]
+    fn ld_bcd_stores_each_digit() {
+        let before = CpuBuilder::new().v(1, 254).i(0x300);
+        let after = before.clone().memory(0x300, &[2, 5, 4]).pc(0x202);
+        assert_opcode(0xF133, &before, &after);
+    }
+
+    #[test]
+    fn ld_set_memory_stores_v0_to_vx() {
+        let before = CpuBuilder::new().v(0, 1).v(1, 2).v(2, 3).v(3, 4).i(0x300);
+        let after = before.clone().memory(0x300, &[1, 2, 3]).pc(0x202);
+        assert_opcode(0xF255, &before, &after);
+    }
+
+    #[test]
+    fn ld_get_memory_reads_v0_to_vx() {
+        let before = CpuBuilder::new().memory(0x300, &[1, 2, 3, 4]).i(0x300);
+        let after = before.clone().v(0, 1).v(1, 2).v(2, 3).pc(0x202);
+        assert_opcode(0xF265, &before, &after);
+    }
+
+    #[test]
+    fn unknown_opcodes_are_skipped() {
+        assert_opcode(0x5121, &CpuBuilder::new(), &CpuBuilder::new().pc(0x202));
+        assert_opcode(0xFFFF, &CpuBuilder::new(), &CpuBuilder::new().pc(0x202));
+    }
+}
@@ -0,0 +1,279 @@
+use core::fmt::{Display, Error, Formatter};
+
+/// A decoded instruction, named after the mnemonics in Cowgod's Chip-8
+/// technical reference.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Instruction {
+    /// `00E0`: clear the display.
+    Cls,
+    /// `00EE`: return from a subroutine.
+    Ret,
+    /// `1nnn`: jump to addr.
+    Jp(u16),
+    /// `2nnn`: call the subroutine at addr.
+    Call(u16),
+    /// `3xkk`: skip if Vx == kk.
+    SeByte(u8, u8),
+    /// `4xkk`: skip if Vx != kk.
+    SneByte(u8, u8),
+    /// `5xy0`: skip if Vx == Vy.
+    SeReg(u8, u8),
+    /// `6xkk`: Vx = kk.
+    LdByte(u8, u8),
+    /// `7xkk`: Vx = Vx + kk.
+    AddByte(u8, u8),
+    /// `8xy0`: Vx = Vy.
+    LdReg(u8, u8),
+    /// `8xy1`: Vx = Vx | Vy.
+    Or(u8, u8),
+    /// `8xy2`: Vx = Vx & Vy.
+    And(u8, u8),
+    /// `8xy3`: Vx = Vx ^ Vy.
+    Xor(u8, u8),
+    /// `8xy4`: Vx = Vx + Vy.
+    AddReg(u8, u8),
+    /// `8xy5`: Vx = Vx - Vy.
+    Sub(u8, u8),
+    /// `8xy6`: Vx = Vx >> 1.
+    Shr(u8, u8),
+    /// `8xy7`: Vx = Vy - Vx.
+    Subn(u8, u8),
+    /// `8xyE`: Vx = Vx << 1.
+    Shl(u8, u8),
+    /// `9xy0`: skip if Vx != Vy.
+    SneReg(u8, u8),
+    /// `Annn`: I = addr.
+    LdI(u16),
+    /// `Bnnn`: jump to V0 + addr.
+    JpV0(u16),
+    /// `Cxkk`: Vx = random & kk.
+    Rnd(u8, u8),
+    /// `Dxyn`: draw an n-byte sprite at (Vx, Vy).
+    Drw(u8, u8, u8),
+    /// `Ex9E`: skip if key Vx is pressed.
+    Skp(u8),
+    /// `ExA1`: skip if key Vx is not pressed.
+    Sknp(u8),
+    /// `Fx07`: Vx = delay timer.
+    LdGetDt(u8),
+    /// `Fx0A`: wait for a key and store it in Vx.
+    LdKey(u8),
+    /// `Fx15`: delay timer = Vx.
+    LdSetDt(u8),
+    /// `Fx18`: sound timer = Vx.
+    LdSetSt(u8),
+    /// `Fx1E`: I = I + Vx.
+    AddI(u8),
+    /// `Fx29`: I = location of the font sprite for Vx.
+    LdSprite(u8),
+    /// `Fx33`: store the BCD of Vx at I.
+    LdBcd(u8),
+    /// `Fx55`: store V0 to Vx at I.
+    LdSetMemory(u8),
+    /// `Fx65`: read V0 to Vx from I.
+    LdGetMemory(u8),
+    /// Anything else, which the CPU treats as a no-op.
+    Unknown(u16),
+}
+
+impl Instruction {
+    /// Decodes the given opcode.
+    pub fn decode(opcode: u16) -> Self {
+        let nibbles = (
+            ((opcode & 0xF000) >> 12) as u8,
+            ((opcode & 0x0F00) >> 8) as u8,
+            ((opcode & 0x00F0) >> 4) as u8,
+            (opcode & 0x000F) as u8,
+        );
+
+        let x = nibbles.1;
+        let y = nibbles.2;
+        let n = nibbles.3;
+        let kk = (opcode & 0x00FF) as u8;
+        let addr = opcode & 0x0FFF;
+
+        match nibbles {
+            (0x00, 0x00, 0x0E, 0x00) => Instruction::Cls,
+            (0x00, 0x00, 0x0E, 0x0E) => Instruction::Ret,
+            (0x01, _, _, _) => Instruction::Jp(addr),
+            (0x02, _, _, _) => Instruction::Call(addr),
+            (0x03, _, _, _) => Instruction::SeByte(x, kk),
+            (0x04, _, _, _) => Instruction::SneByte(x, kk),
+            (0x05, _, _, 0x00) => Instruction::SeReg(x, y),
+            (0x06, _, _, _) => Instruction::LdByte(x, kk),
+            (0x07, _, _, _) => Instruction::AddByte(x, kk),
+            (0x08, _, _, 0x00) => Instruction::LdReg(x, y),
+            (0x08, _, _, 0x01) => Instruction::Or(x, y),
+            (0x08, _, _, 0x02) => Instruction::And(x, y),
+            (0x08, _, _, 0x03) => Instruction::Xor(x, y),
+            (0x08, _, _, 0x04) => Instruction::AddReg(x, y),
+            (0x08, _, _, 0x05) => Instruction::Sub(x, y),
+            (0x08, _, _, 0x06) => Instruction::Shr(x, y),
+            (0x08, _, _, 0x07) => Instruction::Subn(x, y),
+            (0x08, _, _, 0x0E) => Instruction::Shl(x, y),
+            (0x09, _, _, 0x00) => Instruction::SneReg(x, y),
+            (0x0A, _, _, _) => Instruction::LdI(addr),
+            (0x0B, _, _, _) => Instruction::JpV0(addr),
+            (0x0C, _, _, _) => Instruction::Rnd(x, kk),
+            (0x0D, _, _, _) => Instruction::Drw(x, y, n),
+            (0x0E, _, 0x09, 0x0E) => Instruction::Skp(x),
+            (0x0E, _, 0x0A, 0x01) => Instruction::Sknp(x),
+            (0x0F, _, 0x00, 0x07) => Instruction::LdGetDt(x),
+            (0x0F, _, 0x00, 0x0A) => Instruction::LdKey(x),
+            (0x0F, _, 0x01, 0x05) => Instruction::LdSetDt(x),
+            (0x0F, _, 0x01, 0x08) => Instruction::LdSetSt(x),
+            (0x0F, _, 0x01, 0x0E) => Instruction::AddI(x),
+            (0x0F, _, 0x02, 0x09) => Instruction::LdSprite(x),
+            (0x0F, _, 0x03, 0x03) => Instruction::LdBcd(x),
+            (0x0F, _, 0x05, 0x05) => Instruction::LdSetMemory(x),
+            (0x0F, _, 0x06, 0x05) => Instruction::LdGetMemory(x),
+            (_, _, _, _) => Instruction::Unknown(opcode),
+        }
+    }
+
+    /// Returns the form of the instruction, without its operands, e.g.
+    /// `LD Vx, byte`. Instructions of the same type share the same form.
+    pub fn form(&self) -> &'static str {
+        match *self {
+            Instruction::Cls => "CLS",
+            Instruction::Ret => "RET",
+            Instruction::Jp(_) => "JP addr",
+            Instruction::Call(_) => "CALL addr",
+            Instruction::SeByte(..) => "SE Vx, byte",
+            Instruction::SneByte(..) => "SNE Vx, byte",
+            Instruction::SeReg(..) => "SE Vx, Vy",
+            Instruction::LdByte(..) => "LD Vx, byte",
+            Instruction::AddByte(..) => "ADD Vx, byte",
+            Instruction::LdReg(..) => "LD Vx, Vy",
+            Instruction::Or(..) => "OR Vx, Vy",
+            Instruction::And(..) => "AND Vx, Vy",
+            Instruction::Xor(..) => "XOR Vx, Vy",
+            Instruction::AddReg(..) => "ADD Vx, Vy",
+            Instruction::Sub(..) => "SUB Vx, Vy",
+            Instruction::Shr(..) => "SHR Vx, Vy",
+            Instruction::Subn(..) => "SUBN Vx, Vy",
+            Instruction::Shl(..) => "SHL Vx, Vy",
+            Instruction::SneReg(..) => "SNE Vx, Vy",
+            Instruction::LdI(_) => "LD I, addr",
+            Instruction::JpV0(_) => "JP V0, addr",
+            Instruction::Rnd(..) => "RND Vx, byte",
+            Instruction::Drw(..) => "DRW Vx, Vy, n",
+            Instruction::Skp(_) => "SKP Vx",
+            Instruction::Sknp(_) => "SKNP Vx",
+            Instruction::LdGetDt(_) => "LD Vx, DT",
+            Instruction::LdKey(_) => "LD Vx, K",
+            Instruction::LdSetDt(_) => "LD DT, Vx",
+            Instruction::LdSetSt(_) => "LD ST, Vx",
+            Instruction::AddI(_) => "ADD I, Vx",
+            Instruction::LdSprite(_) => "LD F, Vx",
+            Instruction::LdBcd(_) => "LD B, Vx",
+            Instruction::LdSetMemory(_) => "LD [I], Vx",
+            Instruction::LdGetMemory(_) => "LD Vx, [I]",
+            Instruction::Unknown(_) => "DW",
+        }
+    }
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        match *self {
+            Instruction::Cls => write!(f, "CLS"),
+            Instruction::Ret => write!(f, "RET"),
+            Instruction::Jp(addr) => write!(f, "JP {:03X}", addr),
+            Instruction::Call(addr) => write!(f, "CALL {:03X}", addr),
+            Instruction::SeByte(x, kk) => write!(f, "SE V{:X}, {:02X}", x, kk),
+            Instruction::SneByte(x, kk) => write!(f, "SNE V{:X}, {:02X}", x, kk),
+            Instruction::SeReg(x, y) => write!(f, "SE V{:X}, V{:X}", x, y),
+            Instruction::LdByte(x, kk) => write!(f, "LD V{:X}, {:02X}", x, kk),
+            Instruction::AddByte(x, kk) => write!(f, "ADD V{:X}, {:02X}", x, kk),
+            Instruction::LdReg(x, y) => write!(f, "LD V{:X}, V{:X}", x, y),
+            Instruction::Or(x, y) => write!(f, "OR V{:X}, V{:X}", x, y),
+            Instruction::And(x, y) => write!(f, "AND V{:X}, V{:X}", x, y),
+            Instruction::Xor(x, y) => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Instruction::AddReg(x, y) => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Instruction::Sub(x, y) => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Instruction::Shr(x, y) => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Instruction::Subn(x, y) => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Instruction::Shl(x, y) => write!(f, "SHL V{:X}, V{:X}", x, y),
+            Instruction::SneReg(x, y) => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Instruction::LdI(addr) => write!(f, "LD I, {:03X}", addr),
+            Instruction::JpV0(addr) => write!(f, "JP V0, {:03X}", addr),
+            Instruction::Rnd(x, kk) => write!(f, "RND V{:X}, {:02X}", x, kk),
+            Instruction::Drw(x, y, n) => write!(f, "DRW V{:X}, V{:X}, {:X}", x, y, n),
+            Instruction::Skp(x) => write!(f, "SKP V{:X}", x),
+            Instruction::Sknp(x) => write!(f, "SKNP V{:X}", x),
+            Instruction::LdGetDt(x) => write!(f, "LD V{:X}, DT", x),
+            Instruction::LdKey(x) => write!(f, "LD V{:X}, K", x),
+            Instruction::LdSetDt(x) => write!(f, "LD DT, V{:X}", x),
+            Instruction::LdSetSt(x) => write!(f, "LD ST, V{:X}", x),
+            Instruction::AddI(x) => write!(f, "ADD I, V{:X}", x),
+            Instruction::LdSprite(x) => write!(f, "LD F, V{:X}", x),
+            Instruction::LdBcd(x) => write!(f, "LD B, V{:X}", x),
+            Instruction::LdSetMemory(x) => write!(f, "LD [I], V{:X}", x),
+            Instruction::LdGetMemory(x) => write!(f, "LD V{:X}, [I]", x),
+            Instruction::Unknown(opcode) => write!(f, "DW {:04X}", opcode),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disassembles_every_form() {
+        let cases = [
+            (0x00E0, "CLS", "CLS"),
+            (0x00EE, "RET", "RET"),
+            (0x1ABC, "JP ABC", "JP addr"),
+            (0x2ABC, "CALL ABC", "CALL addr"),
+            (0x3A0F, "SE VA, 0F", "SE Vx, byte"),
+            (0x4A0F, "SNE VA, 0F", "SNE Vx, byte"),
+            (0x5AB0, "SE VA, VB", "SE Vx, Vy"),
+            (0x6A0F, "LD VA, 0F", "LD Vx, byte"),
+            (0x7A0F, "ADD VA, 0F", "ADD Vx, byte"),
+            (0x8AB0, "LD VA, VB", "LD Vx, Vy"),
+            (0x8AB1, "OR VA, VB", "OR Vx, Vy"),
+            (0x8AB2, "AND VA, VB", "AND Vx, Vy"),
+            (0x8AB3, "XOR VA, VB", "XOR Vx, Vy"),
+            (0x8AB4, "ADD VA, VB", "ADD Vx, Vy"),
+            (0x8AB5, "SUB VA, VB", "SUB Vx, Vy"),
+            (0x8AB6, "SHR VA, VB", "SHR Vx, Vy"),
+            (0x8AB7, "SUBN VA, VB", "SUBN Vx, Vy"),
+            (0x8ABE, "SHL VA, VB", "SHL Vx, Vy"),
+            (0x9AB0, "SNE VA, VB", "SNE Vx, Vy"),
+            (0xA00F, "LD I, 00F", "LD I, addr"),
+            (0xBABC, "JP V0, ABC", "JP V0, addr"),
+            (0xCA0F, "RND VA, 0F", "RND Vx, byte"),
+            (0xDAB5, "DRW VA, VB, 5", "DRW Vx, Vy, n"),
+            (0xEA9E, "SKP VA", "SKP Vx"),
+            (0xEAA1, "SKNP VA", "SKNP Vx"),
+            (0xFA07, "LD VA, DT", "LD Vx, DT"),
+            (0xFA0A, "LD VA, K", "LD Vx, K"),
+            (0xFA15, "LD DT, VA", "LD DT, Vx"),
+            (0xFA18, "LD ST, VA", "LD ST, Vx"),
+            (0xFA1E, "ADD I, VA", "ADD I, Vx"),
+            (0xFA29, "LD F, VA", "LD F, Vx"),
+            (0xFA33, "LD B, VA", "LD B, Vx"),
+            (0xFA55, "LD [I], VA", "LD [I], Vx"),
+            (0xFA65, "LD VA, [I]", "LD Vx, [I]"),
+            (0x0123, "DW 0123", "DW"),
+        ];
+        for &(opcode, text, form) in cases.iter() {
+            let instruction = Instruction::decode(opcode);
+            assert_eq!(instruction.to_string(), text, "{:04X}", opcode);
+            assert_eq!(instruction.form(), form, "{:04X}", opcode);
+        }
+    }
+
+    #[test]
+    fn opcodes_with_unused_nibbles_set_are_unknown() {
+        for &opcode in [0x00E1, 0x5AB1, 0x8AB8, 0x9AB1, 0xEA9F, 0xFA00, 0xFAFF].iter() {
+            assert_eq!(Instruction::decode(opcode), Instruction::Unknown(opcode));
+            assert_eq!(
+                Instruction::decode(opcode).to_string(),
+                format!("DW {:04X}", opcode)
+            );
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use crate::gpu::Gpu;
+use crate::keypad::Keypad;
+
+/// A request from the user to the emulator, rather than to the ROM.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    /// Stop the emulator.
+    Quit,
+
+    /// Redraw the display, e.g. because the window was resized.
+    Redraw,
+
+    /// Save a screenshot of the display.
+    Screenshot,
+
+    /// Start or stop recording the display.
+    ToggleRecording,
+
+    /// Start or stop fast-forwarding, which runs at the fast-forward
+    /// speed for as long as it's held.
+    FastForward(bool),
+
+    /// Switch between the current speed and running as fast as possible.
+    ToggleUnlimitedSpeed,
+
+    /// Double the speed.
+    SpeedUp,
+
+    /// Halve the speed.
+    SpeedDown,
+
+    /// Pause or resume running frames.
+    TogglePause,
+
+    /// Run a single frame whilst paused.
+    AdvanceFrame,
+
+    /// Reset the CPU and load the ROM again.
+    Reset,
+}
+
+/// Something the display is drawn to.
+pub trait VideoSink {
+    /// Draws the contents of the VRAM. This is called at most once per
+    /// frame, and only when the VRAM has changed or `is_fading` is true.
+    ///
+    /// `graphics.dirty_region()` covers every pixel which has changed
+    /// since the last call, so sinks can redraw just that. It's `None`
+    /// when only fading pixels need redrawing.
+    fn render(&mut self, graphics: &Gpu);
+
+    /// Returns true if the display needs rendering on the next frame even
+    /// if the VRAM hasn't changed.
+    fn is_fading(&self) -> bool {
+        false
+    }
+
+    /// Called at the end of every emulated frame, whether or not it's
+    /// rendered, e.g. to fade pixels at the emulated frame rate.
+    fn end_frame(&mut self, _graphics: &Gpu) {}
+
+    /// Shows the speed multiplier the emulator is running at, or that
+    /// it's running as fast as possible if `None`. This is called
+    /// whenever the speed changes.
+    fn show_speed(&mut self, _speed: Option<f32>) {}
+
+    /// Shows whether the emulator is paused. This is called whenever it's
+    /// paused or resumed.
+    fn show_paused(&mut self, _paused: bool) {}
+}
+
+/// Something which plays the tone whilst the sound timer is running.
+pub trait AudioSink {
+    /// Starts or stops the tone. This is called once per frame.
+    fn set_tone(&mut self, playing: bool);
+}
+
+/// Something which feeds key presses to the keypad.
+pub trait InputSource {
+    /// Feeds any pending key presses and releases to the keypad, stopping
+    /// at and returning the first command given, if any.
+    fn poll(&mut self, keypad: &mut Keypad) -> Option<Command>;
+
+    /// Called at the end of every frame.
+    fn end_frame(&mut self, _keypad: &mut Keypad) {}
+}
+
+/// A display which draws nothing.
+pub struct NullVideo;
+
+impl VideoSink for NullVideo {
+    fn render(&mut self, _graphics: &Gpu) {}
+}
+
+/// A speaker which plays nothing.
+pub struct NullAudio;
+
+impl AudioSink for NullAudio {
+    fn set_tone(&mut self, _playing: bool) {}
+}
+
+/// Input which presses no keys, and quits after a number of frames.
+pub struct FrameLimit {
+    /// The number of frames left to run.
+    frames: u32,
+}
+
+impl FrameLimit {
+    /// Creates an input which quits after the given number of frames.
+    pub fn new(frames: u32) -> Self {
+        FrameLimit { frames }
+    }
+}
+
+impl InputSource for FrameLimit {
+    fn poll(&mut self, _keypad: &mut Keypad) -> Option<Command> {
+        if self.frames == 0 {
+            Some(Command::Quit)
+        } else {
+            None
+        }
+    }
+
+    fn end_frame(&mut self, _keypad: &mut Keypad) {
+        self.frames = self.frames.saturating_sub(1);
+    }
+}
@@ -1,86 +1,414 @@
-use std::fmt::Display;
-use std::fmt::Error;
-use std::fmt::Formatter;
+#[cfg(feature = "std")]
+use crate::palette::Palette;
+use core::fmt::Display;
+use core::fmt::Error;
+use core::fmt::Formatter;
 
 pub const HEIGHT: u32 = 32;
 pub const WIDTH: u32 = 64;
 
+/// How sprites are drawn where they cross the edges of the display.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DrawMode {
+    /// Pixels past an edge wrap around to the opposite edge.
+    #[default]
+    Wrap,
+
+    /// Pixels past the right and bottom edges are dropped, as on the
+    /// COSMAC VIP. The starting coordinate still wraps.
+    Clip,
+}
+
+/// A rectangle of pixels on the display.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    /// The leftmost column.
+    pub x: u32,
+
+    /// The top row.
+    pub y: u32,
+
+    /// The width in pixels.
+    pub width: u32,
+
+    /// The height in pixels.
+    pub height: u32,
+}
+
+impl Rect {
+    /// The whole display.
+    pub const DISPLAY: Rect = Rect {
+        x: 0,
+        y: 0,
+        width: WIDTH,
+        height: HEIGHT,
+    };
+
+    /// Returns the smallest rectangle covering both this one and the
+    /// other.
+    fn union(self, other: Rect) -> Rect {
+        let (left, top) = (self.x.min(other.x), self.y.min(other.y));
+        let right = (self.x + self.width).max(other.x + other.width);
+        let bottom = (self.y + self.height).max(other.y + other.height);
+        Rect {
+            x: left,
+            y: top,
+            width: right - left,
+            height: bottom - top,
+        }
+    }
+}
+
+/// An RGB image of the display.
+#[cfg(feature = "std")]
+pub struct Image {
+    /// The width in pixels.
+    pub width: u32,
+
+    /// The height in pixels.
+    pub height: u32,
+
+    /// The pixels, row by row, as three bytes (red, green, blue) each.
+    pub pixels: Vec<u8>,
+}
+
 pub struct Gpu {
     /// Represents the VRAM.
     ///
     /// This represents a monochrome display of pixels whereby the
-    /// top-left corner is position (0, 0), the top-left is (63, 0)
-    /// and the bottom right is (63, 31).
-    pub memory: [bool; (WIDTH * HEIGHT) as usize],
+    /// top-left corner is position (0, 0), the top-right is (63, 0)
+    /// and the bottom right is (63, 31). Each row is packed into a
+    /// word, with the leftmost pixel in the most significant bit, so a
+    /// row of a sprite is drawn with a single shift and XOR.
+    rows: [u64; HEIGHT as usize],
 
-    /// True if a draw is pending.
-    pub pending_draw: bool,
+    /// The region of the VRAM which has changed since it was last shown,
+    /// or `None` if nothing has.
+    dirty: Option<Rect>,
 }
 
 impl Gpu {
-    /// Creates a new graphics instance
+    /// Creates a new graphics instance. The blank display hasn't been
+    /// shown yet, and so is all dirty.
     pub fn new() -> Self {
         Gpu {
-            memory: [false; (WIDTH * HEIGHT) as usize],
-            pending_draw: false,
+            rows: [0; HEIGHT as usize],
+            dirty: Some(Rect::DISPLAY),
+        }
+    }
+
+    /// Returns true if the pixel at the given position is set.
+    pub fn pixel(&self, x: u32, y: u32) -> bool {
+        (self.rows[y as usize] >> (WIDTH - 1 - x)) & 1 == 1
+    }
+
+    /// Returns the given row of pixels, with the leftmost pixel in the
+    /// most significant bit.
+    pub fn row(&self, y: u32) -> u64 {
+        self.rows[y as usize]
+    }
+
+    /// Returns true if the VRAM has changed since it was last shown.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.is_some()
+    }
+
+    /// Returns the region of the VRAM which has changed since it was last
+    /// shown, or `None` if nothing has.
+    pub fn dirty_region(&self) -> Option<Rect> {
+        self.dirty
+    }
+
+    /// Marks the whole display as changed, e.g. so that it's shown again
+    /// after the window is uncovered.
+    pub fn mark_dirty(&mut self) {
+        self.dirty = Some(Rect::DISPLAY);
+    }
+
+    /// Marks the VRAM as shown. Should be called once the dirty region
+    /// has been redrawn.
+    pub fn clear_dirty(&mut self) {
+        self.dirty = None;
+    }
+
+    /// Returns an image of the VRAM drawn with the given palette, where
+    /// each pixel is drawn as a `scale` by `scale` square.
+    #[cfg(feature = "std")]
+    pub fn to_image(&self, palette: &Palette, scale: u32) -> Image {
+        let (width, height) = (WIDTH * scale, HEIGHT * scale);
+        let mut pixels = Vec::with_capacity((width * height * 3) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let color = palette.color(self.pixel(x / scale, y / scale), false);
+                pixels.extend_from_slice(&[color.0, color.1, color.2]);
+            }
+        }
+        Image {
+            width,
+            height,
+            pixels,
         }
     }
 
     /// Clears the screen and VRAM
     pub fn clear(&mut self) {
-        for y in 0..HEIGHT {
-            for x in 0..WIDTH {
-                self.set_pixel(x as u8, y as u8, false);
+        for y in 0..HEIGHT as usize {
+            let bits = self.rows[y];
+            if bits != 0 {
+                self.rows[y] = 0;
+                self.mark_row(y, bits);
             }
         }
-        self.pending_draw = true;
     }
 
-    /// Stores the given bytes into the given location in memory.
-    /// Returns true if there's a collision (i.e. if an already set
-    /// pixel was unset by a new value).
-    pub fn draw(&mut self, x: u8, y: u8, bytes: Vec<u8>) -> bool {
+    /// Stores the given bytes into the given location in memory, with
+    /// each byte drawn as a row of 8 pixels. Returns true if there's a
+    /// collision (i.e. if an already set pixel was unset by a new value).
+    pub fn draw(&mut self, x: u8, y: u8, bytes: &[u8], mode: DrawMode) -> bool {
+        let (x, y) = (u32::from(x) % WIDTH, u32::from(y) % HEIGHT);
         let mut collision = false;
-        for yy in 0..bytes.len() {
-            for xx in 0..8 {
-                let bit = ((bytes[yy] >> xx) & 0b1) != 0;
-                collision |= self.toggle_pixel(x + 7 - xx, y + yy as u8, bit);
+        for (row, &byte) in bytes.iter().enumerate() {
+            let mut py = y as usize + row;
+            if py >= HEIGHT as usize {
+                if mode == DrawMode::Clip {
+                    break;
+                }
+                py %= HEIGHT as usize;
             }
+
+            // line the byte up with the left edge, then move it across to
+            // x, either wrapping or dropping what goes past the right edge
+            let aligned = u64::from(byte) << (WIDTH - 8);
+            let bits = match mode {
+                DrawMode::Wrap => aligned.rotate_right(x),
+                DrawMode::Clip => aligned >> x,
+            };
+            if bits == 0 {
+                continue;
+            }
+
+            collision |= self.rows[py] & bits != 0;
+            self.rows[py] ^= bits;
+            self.mark_row(py, bits);
         }
         collision
     }
 
-    /// Toggles the value of the given pixel, and returns
-    /// true if the pixel was already set. Resets the pixel
-    /// if it's set twice.
-    fn toggle_pixel(&mut self, x: u8, y: u8, state: bool) -> bool {
-        let index = (y as usize % HEIGHT as usize) * WIDTH as usize + (x as usize % WIDTH as usize);
-        self.pending_draw = self.memory[index] != state;
-        self.memory[index] ^= state;
-        state && !self.memory[index]
+    /// Adds the given pixels of a row, which have just changed, to the
+    /// dirty region.
+    fn mark_row(&mut self, y: usize, bits: u64) {
+        let left = bits.leading_zeros();
+        let right = WIDTH - 1 - bits.trailing_zeros();
+        let changed = Rect {
+            x: left,
+            y: y as u32,
+            width: right - left + 1,
+            height: 1,
+        };
+        self.dirty = Some(match self.dirty {
+            Some(dirty) => dirty.union(changed),
+            None => changed,
+        });
     }
+}
 
-    /// Sets the value of the given pixel, ignoring what's already there.
-    fn set_pixel(&mut self, x: u8, y: u8, state: bool) {
-        let index = (y as usize % HEIGHT as usize) * WIDTH as usize + (x as usize % WIDTH as usize);
-        self.pending_draw = self.memory[index] != state;
-        self.memory[index] = state;
+impl Default for Gpu {
+    fn default() -> Self {
+        Gpu::new()
     }
 }
 
 impl Display for Gpu {
     fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
-        let mut str = String::new();
-        str.push_str("\n");
+        writeln!(f)?;
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let strbit = if self.pixel(x, y) { "X" } else { "O" };
+                f.write_str(strbit)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Draws the sprite on a blank display, where it can't collide, and
+    /// returns the pixels it set.
+    fn draw(x: u8, y: u8, bytes: &[u8], mode: DrawMode) -> Vec<(u32, u32)> {
+        let mut gpu = Gpu::new();
+        assert!(!gpu.draw(x, y, bytes, mode));
+        lit(&gpu)
+    }
+
+    /// Returns the coordinates of every set pixel, row by row.
+    fn lit(gpu: &Gpu) -> Vec<(u32, u32)> {
+        let mut pixels = Vec::new();
         for y in 0..HEIGHT {
             for x in 0..WIDTH {
-                let index = y as usize * WIDTH as usize + x as usize;
-                let bit = self.memory[index];
-                let strbit = if bit { "X" } else { "O" };
-                str.push_str(strbit.as_ref());
+                if gpu.pixel(x, y) {
+                    pixels.push((x, y));
+                }
+            }
+        }
+        pixels
+    }
+
+    #[test]
+    fn starts_dirty_until_shown() {
+        let mut gpu = Gpu::new();
+        assert_eq!(gpu.dirty_region(), Some(Rect::DISPLAY));
+        gpu.clear_dirty();
+        assert!(!gpu.is_dirty());
+    }
+
+    #[test]
+    fn unchanged_pixels_keep_the_display_dirty() {
+        let mut gpu = Gpu::new();
+        gpu.clear_dirty();
+
+        // the set pixel comes before the unchanged ones after it
+        gpu.draw(0, 0, &[0b1000_0000, 0b0000_0000], DrawMode::Wrap);
+        assert!(gpu.is_dirty());
+    }
+
+    #[test]
+    fn only_changes_make_the_display_dirty() {
+        let mut gpu = Gpu::new();
+        gpu.clear_dirty();
+
+        gpu.draw(10, 10, &[0b0000_0000; 4], DrawMode::Wrap);
+        gpu.clear();
+        assert!(!gpu.is_dirty());
+    }
+
+    #[test]
+    fn dirty_region_covers_every_change() {
+        let mut gpu = Gpu::new();
+        gpu.draw(10, 5, &[0b1000_0000], DrawMode::Wrap);
+        gpu.clear_dirty();
+
+        gpu.draw(20, 8, &[0b0000_0000, 0b0100_0001], DrawMode::Wrap);
+        let expected = Rect {
+            x: 21,
+            y: 9,
+            width: 7,
+            height: 1,
+        };
+        assert_eq!(gpu.dirty_region(), Some(expected));
+
+        gpu.clear();
+        let expected = Rect {
+            x: 10,
+            y: 5,
+            width: 18,
+            height: 5,
+        };
+        assert_eq!(gpu.dirty_region(), Some(expected));
+    }
+
+    #[test]
+    fn draws_the_most_significant_bit_leftmost() {
+        let pixels = draw(10, 5, &[0b1000_0001], DrawMode::Wrap);
+        assert_eq!(pixels, vec![(10, 5), (17, 5)]);
+    }
+
+    #[test]
+    fn sprites_crossing_the_right_edge() {
+        let wrapped = draw(62, 0, &[0b1111_0000], DrawMode::Wrap);
+        assert_eq!(wrapped, vec![(0, 0), (1, 0), (62, 0), (63, 0)]);
+
+        let clipped = draw(62, 0, &[0b1111_0000], DrawMode::Clip);
+        assert_eq!(clipped, vec![(62, 0), (63, 0)]);
+    }
+
+    #[test]
+    fn sprites_crossing_the_bottom_edge() {
+        let sprite = [0b1000_0000; 4];
+        let wrapped = draw(0, 30, &sprite, DrawMode::Wrap);
+        assert_eq!(wrapped, vec![(0, 0), (0, 1), (0, 30), (0, 31)]);
+
+        let clipped = draw(0, 30, &sprite, DrawMode::Clip);
+        assert_eq!(clipped, vec![(0, 30), (0, 31)]);
+    }
+
+    #[test]
+    fn sprites_crossing_the_bottom_right_corner() {
+        let sprite = [0b1111_0000; 2];
+        let wrapped = draw(62, 31, &sprite, DrawMode::Wrap);
+        let expected = vec![(0, 0), (1, 0), (62, 0), (63, 0)];
+        let expected = [expected, vec![(0, 31), (1, 31), (62, 31), (63, 31)]].concat();
+        assert_eq!(wrapped, expected);
+
+        let clipped = draw(62, 31, &sprite, DrawMode::Clip);
+        assert_eq!(clipped, vec![(62, 31), (63, 31)]);
+    }
+
+    #[test]
+    fn starting_coordinates_wrap_in_both_modes() {
+        // the largest coordinates a register can hold start at the
+        // bottom right, and mustn't overflow
+        let wrapped = draw(255, 255, &[0b1100_0000; 2], DrawMode::Wrap);
+        assert_eq!(wrapped, vec![(0, 0), (63, 0), (0, 31), (63, 31)]);
+
+        let clipped = draw(255, 255, &[0b1100_0000; 2], DrawMode::Clip);
+        assert_eq!(clipped, vec![(63, 31)]);
+
+        let clipped = draw(64 + 3, 32 + 4, &[0b1000_0000], DrawMode::Clip);
+        assert_eq!(clipped, vec![(3, 4)]);
+    }
+
+    #[test]
+    fn clipped_pixels_never_collide() {
+        let mut gpu = Gpu::new();
+        gpu.draw(0, 0, &[0b1000_0000], DrawMode::Wrap);
+
+        assert!(!gpu.draw(63, 0, &[0b1100_0000], DrawMode::Clip));
+        assert_eq!(lit(&gpu), vec![(0, 0), (63, 0)]);
+
+        assert!(gpu.draw(63, 0, &[0b1100_0000], DrawMode::Wrap));
+        assert_eq!(lit(&gpu), vec![]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn images_are_scaled_and_drawn_with_the_palette() {
+        use crate::palette::Rgb;
+
+        let palette = Palette {
+            colors: [
+                Rgb(0x10, 0x20, 0x30),
+                Rgb(0xA0, 0xB0, 0xC0),
+                Rgb(0, 0, 0),
+                Rgb(0, 0, 0),
+            ],
+        };
+        let mut gpu = Gpu::new();
+        gpu.draw(1, 0, &[0b1000_0000], DrawMode::Wrap);
+        gpu.draw(63, 31, &[0b1000_0000], DrawMode::Wrap);
+
+        let image = gpu.to_image(&palette, 3);
+        assert_eq!((image.width, image.height), (WIDTH * 3, HEIGHT * 3));
+        assert_eq!(image.pixels.len(), (WIDTH * 3 * HEIGHT * 3 * 3) as usize);
+
+        // each pixel is a 3 by 3 square, row by row
+        let color = |x: u32, y: u32| {
+            let offset = ((y * image.width + x) * 3) as usize;
+            &image.pixels[offset..offset + 3]
+        };
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let lit = (x / 3, y / 3) == (1, 0) || (x / 3, y / 3) == (63, 31);
+                let expected: &[u8] = if lit {
+                    &[0xA0, 0xB0, 0xC0]
+                } else {
+                    &[0x10, 0x20, 0x30]
+                };
+                assert_eq!(color(x, y), expected, "pixel ({}, {})", x, y);
             }
-            str.push_str("\n");
         }
-        write!(f, "{}", str)
     }
 }
@@ -1,13 +1,10 @@
-use sdl2::keyboard::Keycode;
-use std::mem::discriminant;
-
 /// The keymap to use.
 pub enum KeypadSetting {
     /// DVORAK bindings.
-    DVORAK,
+    Dvorak,
 
     /// Qwerty Bindings.
-    QWERTY,
+    Qwerty,
 }
 
 /// Represents a keypad.
@@ -15,7 +12,7 @@ pub struct Keypad {
     /// The state of the 16 keys.
     ///
     /// These have the following layout:
-    /// ```
+    /// ```text
     ///   1 2 3 C
     ///   4 5 6 D
     ///   7 8 9 E
@@ -24,6 +21,15 @@ pub struct Keypad {
     /// The value is true if the key is pressed.
     pub keys: [bool; 16],
 
+    /// Keys which went down since the edges were last cleared.
+    ///
+    /// A key which is pressed and released between two frames is
+    /// still reported here, so short taps are never dropped.
+    pub pressed: [bool; 16],
+
+    /// Keys which went up since the edges were last cleared.
+    pub released: [bool; 16],
+
     /// The keypad setting
     pub setting: KeypadSetting,
 }
@@ -32,69 +38,68 @@ impl Keypad {
     pub fn new(setting: KeypadSetting) -> Self {
         Keypad {
             keys: [false; 16],
+            pressed: [false; 16],
+            released: [false; 16],
             setting,
         }
     }
 
-    pub fn clear(&mut self) {
-        for i in 0..self.keys.len() {
-            self.keys[i] = false;
-        }
+    /// Forgets the press and release edges seen so far.
+    ///
+    /// The held state in `keys` is left untouched.
+    pub fn clear_edges(&mut self) {
+        self.pressed = [false; 16];
+        self.released = [false; 16];
     }
 
-    /// Returns true if the given key index is pressed.
+    /// Returns true if the given key index is held, or was pressed
+    /// since the edges were last cleared.
     pub fn is_key_pressed(&self, key: u8) -> bool {
-        self.keys[key as usize]
+        self.keys[key as usize] || self.pressed[key as usize]
     }
 
-    /// Maps the given pressed keyboard-key to an index
-    pub fn set_from_keycode(&mut self, key: Keycode, state: bool) {
-        if discriminant(&self.setting) == discriminant(&KeypadSetting::DVORAK) {
-            match key {
-                Keycode::Num1 => self.keys[0x1 as usize] = state,
-                Keycode::Num2 => self.keys[0x2 as usize] = state,
-                Keycode::Num3 => self.keys[0x3 as usize] = state,
-                Keycode::Num4 => self.keys[0xC as usize] = state,
-
-                Keycode::Quote => self.keys[0x4 as usize] = state,
-                Keycode::Comma => self.keys[0x5 as usize] = state,
-                Keycode::Period => self.keys[0x6 as usize] = state,
-                Keycode::P => self.keys[0xD as usize] = state,
-
-                Keycode::A => self.keys[0x7 as usize] = state,
-                Keycode::O => self.keys[0x8 as usize] = state,
-                Keycode::E => self.keys[0x9 as usize] = state,
-                Keycode::U => self.keys[0xE as usize] = state,
-
-                Keycode::Semicolon => self.keys[0xA as usize] = state,
-                Keycode::Q => self.keys[0x0 as usize] = state,
-                Keycode::J => self.keys[0xB as usize] = state,
-                Keycode::K => self.keys[0xF as usize] = state,
-                _ => (),
-            }
-        } else {
-            match key {
-                Keycode::Num1 => self.keys[0x1 as usize] = state,
-                Keycode::Num2 => self.keys[0x2 as usize] = state,
-                Keycode::Num3 => self.keys[0x3 as usize] = state,
-                Keycode::Num4 => self.keys[0xC as usize] = state,
-
-                Keycode::Q => self.keys[0x4 as usize] = state,
-                Keycode::W => self.keys[0x5 as usize] = state,
-                Keycode::E => self.keys[0x6 as usize] = state,
-                Keycode::R => self.keys[0xD as usize] = state,
+    /// Returns the lowest key index which was released since the edges
+    /// were last cleared, if any, and forgets that release so it is only
+    /// reported once.
+    pub fn take_released_key(&mut self) -> Option<u8> {
+        let key = self.released.iter().position(|key| *key)?;
+        self.released[key] = false;
+        Some(key as u8)
+    }
 
-                Keycode::A => self.keys[0x7 as usize] = state,
-                Keycode::S => self.keys[0x8 as usize] = state,
-                Keycode::D => self.keys[0x9 as usize] = state,
-                Keycode::F => self.keys[0xE as usize] = state,
+    /// Marks the given key index as pressed, recording the edge.
+    pub fn key_down(&mut self, key: u8) {
+        if !self.keys[key as usize] {
+            self.pressed[key as usize] = true;
+        }
+        self.keys[key as usize] = true;
+    }
 
-                Keycode::Z => self.keys[0xA as usize] = state,
-                Keycode::X => self.keys[0x0 as usize] = state,
-                Keycode::C => self.keys[0xB as usize] = state,
-                Keycode::V => self.keys[0xF as usize] = state,
-                _ => (),
-            }
+    /// Marks the given key index as released, recording the edge.
+    pub fn key_up(&mut self, key: u8) {
+        if self.keys[key as usize] {
+            self.released[key as usize] = true;
         }
+        self.keys[key as usize] = false;
+    }
+
+    /// Maps the given typed character to a key index
+    pub fn map_char(&self, c: char) -> Option<u8> {
+        let layout = match self.setting {
+            KeypadSetting::Dvorak => &DVORAK_LAYOUT,
+            KeypadSetting::Qwerty => &QWERTY_LAYOUT,
+        };
+        let c = c.to_ascii_lowercase();
+        layout.iter().position(|key| *key == c).map(|key| key as u8)
     }
 }
+
+/// The keyboard characters for each key index with QWERTY bindings.
+const QWERTY_LAYOUT: [char; 16] = [
+    'x', '1', '2', '3', 'q', 'w', 'e', 'a', 's', 'd', 'z', 'c', '4', 'r', 'f', 'v',
+];
+
+/// The keyboard characters for each key index with DVORAK bindings.
+const DVORAK_LAYOUT: [char; 16] = [
+    'q', '1', '2', '3', '\'', ',', '.', 'a', 'o', 'e', ';', 'j', '4', 'p', 'u', 'k',
+];
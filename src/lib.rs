@@ -0,0 +1,24 @@
+//! The emulator core: the CPU, display memory and keypad, along with the
+//! traits frontends implement to plug into the shared run loop.
+//!
+//! With the default `std` feature disabled, the crate is `no_std` and
+//! needs no allocator. Random bytes then come from the closure passed to
+//! `Cpu::with_rng`, and the run loop and frame scheduler aren't available.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+pub mod cpu;
+pub mod disasm;
+pub mod frontend;
+pub mod gpu;
+pub mod keypad;
+pub mod palette;
+pub mod persistence;
+#[cfg(feature = "std")]
+pub mod profiler;
+pub mod quirks;
+pub mod roms;
+#[cfg(feature = "std")]
+pub mod runner;
+#[cfg(feature = "std")]
+pub mod scheduler;
@@ -1,24 +1,42 @@
-mod cpu;
-mod gpu;
-mod io;
-mod keypad;
-mod roms;
-
-use crate::cpu::Cpu;
-use crate::gpu::Gpu;
-use crate::io::Display;
-use crate::keypad::{Keypad, KeypadSetting};
+mod config;
+mod recorder;
+mod screenshot;
+mod sdl;
+mod trace;
+mod tui;
+mod watch;
+
+use crate::config::Config;
+use crate::recorder::GifRecorder;
+use crate::trace::Tracer;
+use crate::tui::TerminalDisplay;
+use crate::watch::RomWatcher;
+use chip8::cpu::Cpu;
+use chip8::frontend::{AudioSink, Command, FrameLimit, InputSource, NullAudio, NullVideo};
+use chip8::gpu::Gpu;
+use chip8::keypad::{Keypad, KeypadSetting};
+use chip8::palette::{self, Palette};
+use chip8::persistence::Persistence;
+use chip8::profiler::Profiler;
+use chip8::quirks::Quirks;
+use chip8::roms;
+use chip8::runner::{Observer, Runner};
+use chip8::scheduler::{self, FrameScheduler};
 use clap::{App, Arg};
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
-use std::error::Error;
-use std::time::{Duration, SystemTime};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
 
 fn main() {
     env_logger::init();
 
     const VERSION: &str = env!("CARGO_PKG_VERSION");
     let rom_names = roms::ROMS.iter().map(|rom| rom.name).collect::<Vec<&str>>();
+    let rom_help = format!(
+        "The ROM to run: a built-in ROM ({}) or the path to a ROM file",
+        rom_names.join(", ")
+    );
 
     let matches = App::new("Chip 8 Emulator")
         .version(VERSION)
@@ -28,9 +46,13 @@ fn main() {
             Arg::with_name("rom")
                 .display_order(1)
                 .value_name("ROM")
-                .help("The rom to use")
-                .required(true)
-                .possible_values(&rom_names),
+                .help(&rom_help)
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("watch")
+                .help("Resets and loads the ROM file again whenever it changes")
+                .long("watch"),
         )
         .arg(
             Arg::with_name("keymap")
@@ -43,6 +65,121 @@ fn main() {
                 .short("k")
                 .long("keymap"),
         )
+        .arg(
+            Arg::with_name("palette")
+                .help("The palette to draw with: a preset, or 2 to 4 hex colours")
+                .long("palette")
+                .short("p")
+                .takes_value(true)
+                .validator(|val| Palette::parse(&val).map(|_| ()))
+                .value_name("PALETTE"),
+        )
+        .arg(
+            Arg::with_name("persistence")
+                .help("How pixels fade out after being switched off")
+                .long("persistence")
+                .takes_value(true)
+                .possible_values(&["off", "decay", "blend"])
+                .value_name("PERSISTENCE"),
+        )
+        .arg(
+            Arg::with_name("quirks")
+                .help("The interpreter whose behaviours to follow")
+                .long("quirks")
+                .takes_value(true)
+                .possible_values(&["modern", "vip"])
+                .value_name("QUIRKS"),
+        )
+        .arg(
+            Arg::with_name("screenshot-dir")
+                .help("The directory to save screenshots and recordings to")
+                .long("screenshot-dir")
+                .takes_value(true)
+                .value_name("DIR"),
+        )
+        .arg(
+            Arg::with_name("screenshot-scale")
+                .help("The scale to save screenshots and recordings at")
+                .long("screenshot-scale")
+                .takes_value(true)
+                .validator(|val| screenshot::parse_scale(&val).map(|_| ()))
+                .value_name("SCALE"),
+        )
+        .arg(
+            Arg::with_name("record")
+                .help("Records the display to the given GIF file")
+                .long("record")
+                .short("r")
+                .takes_value(true)
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::with_name("trace")
+                .help("Writes a line to the given file for every instruction executed")
+                .long("trace")
+                .takes_value(true)
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::with_name("trace-addresses")
+                .help("Only traces instructions within the given hex address range")
+                .long("trace-addresses")
+                .takes_value(true)
+                .requires("trace")
+                .validator(|val| {
+                    trace::parse_range(&val, trace::parse_address, 0, u16::MAX).map(|_| ())
+                })
+                .value_name("START-END"),
+        )
+        .arg(
+            Arg::with_name("trace-cycles")
+                .help("Only traces instructions within the given cycle range")
+                .long("trace-cycles")
+                .takes_value(true)
+                .requires("trace")
+                .validator(|val| {
+                    trace::parse_range(&val, trace::parse_cycle, 0, u64::MAX).map(|_| ())
+                })
+                .value_name("START-END"),
+        )
+        .arg(
+            Arg::with_name("profile")
+                .help("Writes a report of where time was spent to the given file on exit")
+                .long("profile")
+                .takes_value(true)
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::with_name("profile-format")
+                .help("The format of the profile report")
+                .long("profile-format")
+                .default_value("text")
+                .takes_value(true)
+                .possible_values(&["text", "json"])
+                .value_name("FORMAT"),
+        )
+        .arg(
+            Arg::with_name("headless")
+                .help("Runs without a window for the given number of frames")
+                .long("headless")
+                .requires("frames"),
+        )
+        .arg(
+            Arg::with_name("frames")
+                .help("The number of frames to run when headless")
+                .long("frames")
+                .takes_value(true)
+                .validator(|val| validate_int(&val))
+                .value_name("FRAMES"),
+        )
+        .arg(
+            Arg::with_name("config")
+                .help("The config file to use")
+                .long("config")
+                .short("c")
+                .takes_value(true)
+                .value_name("CONFIG"),
+        )
         .arg(
             Arg::with_name("scale")
                 .help("The scale of the display")
@@ -53,92 +190,456 @@ fn main() {
                 .validator(|val| validate_int(&val))
                 .value_name("SCALE"),
         )
+        .arg(
+            Arg::with_name("frontend")
+                .help("The frontend to display the emulator with")
+                .long("frontend")
+                .short("f")
+                .default_value("sdl")
+                .takes_value(true)
+                .possible_values(&["sdl", "tui"])
+                .value_name("FRONTEND"),
+        )
+        .arg(
+            Arg::with_name("ipf")
+                .help("The number of instructions to run per frame")
+                .long("ipf")
+                .default_value("8")
+                .takes_value(true)
+                .validator(|val| validate_int(&val))
+                .value_name("IPF"),
+        )
         .arg(
             Arg::with_name("multiplier")
                 .help("The clock speed multiplier")
                 .short("m")
                 .long("multiplier")
                 .takes_value(true)
-                .validator(|val| validate_float(&val))
+                .validator(|val| scheduler::parse_multiplier(&val).map(|_| ()))
                 .default_value("1.0")
                 .value_name("MULTIPLIER"),
         )
+        .arg(
+            Arg::with_name("fast-forward")
+                .help("The speed multiplier to fast-forward at while Tab is held")
+                .long("fast-forward")
+                .takes_value(true)
+                .validator(|val| scheduler::parse_speed(&val).map(|_| ()))
+                .default_value("unlimited")
+                .value_name("MULTIPLIER"),
+        )
         .get_matches();
 
     // this verification should be handled by Clap, but just in case...
-    let rom_name = matches.value_of("rom").expect("Missing ROM");
+    let rom_arg = matches.value_of("rom").expect("Missing ROM");
     let scale = matches
         .value_of("scale")
         .expect("Missing scale")
         .parse()
         .expect("Invalid scale");
-    let speed_multiplier: f32 = matches
-        .value_of("multiplier")
-        .expect("Missing multiplier")
+    let instructions_per_frame: u32 = matches
+        .value_of("ipf")
+        .expect("Missing instructions per frame")
         .parse()
-        .expect("Invalid multiplier");
+        .expect("Invalid instructions per frame");
+    let speed_multiplier = matches
+        .value_of("multiplier")
+        .map(|val| scheduler::parse_multiplier(val).unwrap())
+        .expect("Missing multiplier");
+    let fast_forward_speed = matches
+        .value_of("fast-forward")
+        .map(|val| scheduler::parse_speed(val).unwrap())
+        .expect("Missing fast-forward speed");
     let keymap = matches.value_of("keymap").expect("Missing keypad");
 
-    let rom = roms::ROMS
-        .iter()
-        .find(|x| x.name == rom_name)
-        .map(|x| x.data)
-        .unwrap(); // impossible to happen
+    // a ROM is either built in, or read from a file named after it
+    let builtin = roms::ROMS.iter().find(|rom| rom.name == rom_arg);
+    let (rom_name, rom_path, mut rom_data) = match builtin {
+        Some(rom) => (rom.name.to_owned(), None, rom.data.to_vec()),
+        None => {
+            let path = PathBuf::from(rom_arg);
+            let data = watch::read_rom(&path).unwrap_or_else(|err| {
+                eprintln!("Failed to load ROM {}", err);
+                process::exit(1);
+            });
+            let name = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_else(|| String::from("rom"));
+            (name, Some(path), data)
+        }
+    };
+    let watch_path = match (&rom_path, matches.is_present("watch")) {
+        (Some(path), true) => Some(path.clone()),
+        (None, true) => {
+            eprintln!(
+                "Failed to watch ROM {}, only ROM files can be watched",
+                rom_name
+            );
+            process::exit(1);
+        }
+        (_, false) => None,
+    };
+
+    let config_path = matches
+        .value_of("config")
+        .map(PathBuf::from)
+        .or_else(Config::default_path);
+    let config = match config_path {
+        Some(path) => Config::load(&path).unwrap_or_else(|err| {
+            eprintln!("Failed to load config file {}", err);
+            process::exit(1);
+        }),
+        None => Config::default(),
+    };
+
+    // the command line takes precedence over the config file, which
+    // takes precedence over the ROM's own settings
+    let palette = matches
+        .value_of("palette")
+        .map(|val| Palette::parse(val).unwrap())
+        .or(config.palette)
+        .or(builtin.and_then(|rom| rom.palette))
+        .unwrap_or(palette::CLASSIC);
+    let persistence = matches
+        .value_of("persistence")
+        .map(|val| Persistence::parse(val).unwrap())
+        .or(config.persistence)
+        .or(builtin.and_then(|rom| rom.persistence))
+        .unwrap_or(Persistence::Off);
+    let quirks = matches
+        .value_of("quirks")
+        .map(|val| Quirks::parse(val).unwrap())
+        .or(config.quirks)
+        .or(builtin.and_then(|rom| rom.quirks))
+        .unwrap_or_default();
+    let screenshot_dir = matches
+        .value_of("screenshot-dir")
+        .map(PathBuf::from)
+        .or(config.screenshot_dir)
+        .unwrap_or_else(|| PathBuf::from("."));
+    let screenshot_scale: u32 = matches
+        .value_of("screenshot-scale")
+        .map(|val| screenshot::parse_scale(val).unwrap())
+        .or(config.screenshot_scale)
+        .unwrap_or(1);
 
     // initialise dependencies
     let gpu = Gpu::new();
     let keypad = Keypad::new(if keymap == "dvorak" {
-        KeypadSetting::DVORAK
+        KeypadSetting::Dvorak
     } else {
-        KeypadSetting::QWERTY
+        KeypadSetting::Qwerty
     });
 
     // initialise CPU
     let mut cpu = Cpu::new(gpu, keypad);
-    cpu.load_rom(rom);
+    cpu.quirks = quirks;
+    cpu.load_rom(&rom_data).unwrap_or_else(|err| {
+        eprintln!("Failed to load ROM {}", err);
+        process::exit(1);
+    });
+
+    let recorder = matches.value_of("record").map(|path| {
+        GifRecorder::create(Path::new(path), &palette, screenshot_scale).unwrap_or_else(|err| {
+            eprintln!("Failed to start recording {}", err);
+            process::exit(1);
+        })
+    });
+    let tracer = matches.value_of("trace").map(|path| {
+        let addresses = matches
+            .value_of("trace-addresses")
+            .map(|val| trace::parse_range(val, trace::parse_address, 0, u16::MAX).unwrap())
+            .unwrap_or(0..=u16::MAX);
+        let cycles = matches
+            .value_of("trace-cycles")
+            .map(|val| trace::parse_range(val, trace::parse_cycle, 0, u64::MAX).unwrap())
+            .unwrap_or(0..=u64::MAX);
+        Tracer::create(Path::new(path), addresses, cycles).unwrap_or_else(|err| {
+            eprintln!("Failed to start tracing {}", err);
+            process::exit(1);
+        })
+    });
+    let profiler = matches
+        .value_of("profile")
+        .map(|_| Profiler::new(rom_data.len()));
+    let tui = !matches.is_present("headless") && matches.value_of("frontend") == Some("tui");
+    let mut outputs = Outputs {
+        recorder,
+        tracer,
+        profiler,
+        messages: Messages::new(tui),
+    };
+
+    // reset whenever the ROM file changes, if asked to
+    let watched = |input: Box<dyn InputSource>| -> Box<dyn InputSource> {
+        match &watch_path {
+            Some(path) => Box::new(RomWatcher::new(input, path.clone())),
+            None => input,
+        }
+    };
 
-    // Initialise display/SDL2
-    let sdl_context = sdl2::init().expect("SDL2 is not installed");
-    let mut display = Display::new(&sdl_context, scale);
-    let mut events = sdl_context.event_pump().unwrap();
+    // keep the SDL context alive for as long as the frontend uses it
+    let sdl_context;
+    let mut runner = if matches.is_present("headless") {
+        // run as fast as possible without a window, only capturing frames
+        let frames = matches
+            .value_of("frames")
+            .expect("Missing frames")
+            .parse()
+            .expect("Invalid frames");
+        Runner::new(
+            Box::new(NullVideo),
+            Box::new(NullAudio),
+            watched(Box::new(FrameLimit::new(frames))),
+            FrameScheduler::uncapped(),
+            instructions_per_frame,
+        )
+    } else if tui {
+        // draw into the terminal rather than a window
+        let display = TerminalDisplay::new(palette).unwrap_or_else(|err| {
+            eprintln!("Failed to set up the terminal {}", err);
+            process::exit(1);
+        });
+        let input = display.input();
+        Runner::new(
+            Box::new(display),
+            Box::new(NullAudio),
+            watched(Box::new(input)),
+            FrameScheduler::new(speed_multiplier),
+            instructions_per_frame,
+        )
+    } else {
+        sdl_context = sdl2::init().expect("SDL2 is not installed");
+        let display = sdl::Display::new(&sdl_context, scale, palette, persistence);
+        let audio: Box<dyn AudioSink> = match sdl::Audio::new(&sdl_context) {
+            Ok(audio) => Box::new(audio),
+            Err(err) => {
+                eprintln!("Failed to open audio, continuing without sound {}", err);
+                Box::new(NullAudio)
+            }
+        };
+        Runner::new(
+            Box::new(display),
+            audio,
+            watched(Box::new(sdl::Input::new(&sdl_context))),
+            FrameScheduler::new(speed_multiplier),
+            instructions_per_frame,
+        )
+    };
 
-    // CPU should run at 500Hz, so one iteration every 2,000,000ns
-    let tick_duration_ns = (2_000_000.0 / speed_multiplier) as u64;
+    runner.set_fast_forward_speed(fast_forward_speed);
 
+    // run frames at 60Hz, each executing a fixed number of instructions
     loop {
-        let start_time = SystemTime::now();
+        let command = runner.run(&mut cpu, &mut outputs);
+
+        match command {
+            Command::Screenshot => {
+                let image = cpu.gpu.to_image(&palette, screenshot_scale);
+                match screenshot::save_screenshot(&image, &screenshot_dir, &rom_name) {
+                    Ok(path) => outputs
+                        .messages
+                        .info(format!("Saved screenshot to {}", path.display())),
+                    Err(err) => outputs
+                        .messages
+                        .error(format!("Failed to save screenshot {}", err)),
+                }
+            }
+            Command::ToggleRecording => {
+                outputs.recorder = match outputs.recorder.take() {
+                    Some(recorder) => {
+                        stop_recording(Some(recorder), &mut outputs.messages);
+                        None
+                    }
+                    None => {
+                        let recorder = screenshot::output_path(&screenshot_dir, &rom_name, "gif")
+                            .and_then(|path| {
+                                GifRecorder::create(&path, &palette, screenshot_scale)
+                                    .map(|recorder| (path, recorder))
+                            });
+                        match recorder {
+                            Ok((path, recorder)) => {
+                                outputs
+                                    .messages
+                                    .info(format!("Recording to {}", path.display()));
+                                Some(recorder)
+                            }
+                            Err(err) => {
+                                outputs
+                                    .messages
+                                    .error(format!("Failed to start recording {}", err));
+                                None
+                            }
+                        }
+                    }
+                }
+            }
+            Command::Reset => {
+                // pick up any changes to the ROM file
+                if let Some(path) = &rom_path {
+                    match watch::read_rom(path) {
+                        Ok(data) => {
+                            outputs.messages.info(format!("Loaded {}", path.display()));
+                            rom_data = data;
+                        }
+                        Err(err) => outputs
+                            .messages
+                            .error(format!("Failed to reload ROM {}", err)),
+                    }
+                }
+                cpu.reset();
+                cpu.load_rom(&rom_data)
+                    .expect("ROM files are checked when read");
+                outputs.reset(rom_data.len());
+            }
+            Command::Redraw
+            | Command::FastForward(_)
+            | Command::ToggleUnlimitedSpeed
+            | Command::SpeedUp
+            | Command::SpeedDown
+            | Command::TogglePause
+            | Command::AdvanceFrame => (),
+            Command::Quit => break,
+        }
+    }
 
-        // quit gracefully if quit event is pushed
-        for event in events.poll_iter() {
-            if let Event::Quit { .. } = event {
-                return;
-            };
+    // restore the terminal before reporting on the outputs
+    drop(runner);
+    outputs.messages.release();
+    stop_recording(outputs.recorder, &mut outputs.messages);
+    match outputs.tracer.map(Tracer::finish) {
+        Some(Ok(path)) => println!("Saved trace to {}", path.display()),
+        Some(Err(err)) => eprintln!("Failed to save trace {}", err),
+        None => (),
+    }
+    if let (Some(profiler), Some(path)) = (outputs.profiler, matches.value_of("profile")) {
+        let json = matches.value_of("profile-format") == Some("json");
+        match save_profile(&profiler, Path::new(path), json) {
+            Ok(()) => println!("Saved profile to {}", path),
+            Err(err) => eprintln!("Failed to save profile {}: {}", path, err),
         }
+    }
+}
+
+/// Writes the profiler's report to the given file, as JSON or text.
+fn save_profile(profiler: &Profiler, path: &Path, json: bool) -> std::io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    if json {
+        profiler.write_json(&mut out)?;
+    } else {
+        profiler.write_text(&mut out)?;
+    }
+    out.flush()
+}
+
+/// The files written to as the emulator runs.
+struct Outputs {
+    /// The recording of the display, if one is running.
+    recorder: Option<GifRecorder>,
+
+    /// The instruction trace, if one was asked for.
+    tracer: Option<Tracer>,
 
-        let keys: Vec<Keycode> = events
-            .keyboard_state()
-            .pressed_scancodes()
-            .filter_map(Keycode::from_scancode)
-            .collect();
+    /// The profiler, if a profile was asked for.
+    profiler: Option<Profiler>,
 
-        // clear keycodes and then set the pressed keys
-        cpu.keypad.clear();
-        for key in keys {
-            cpu.keypad.set_from_keycode(key, true);
+    /// Where messages about the outputs are reported.
+    messages: Messages,
+}
+
+impl Outputs {
+    /// Marks the reset in the trace, and starts a new profile of the
+    /// program loaded since.
+    fn reset(&mut self, program_len: usize) {
+        if let Some(Err(err)) = self.tracer.as_mut().map(Tracer::reset) {
+            self.messages
+                .error(format!("Failed to trace reset {}", err));
+            self.tracer = None;
         }
+        if let Some(profiler) = self.profiler.as_mut() {
+            *profiler = Profiler::new(program_len);
+        }
+    }
+}
+
+impl Observer for Outputs {
+    fn before_cycle(&mut self, cpu: &Cpu) {
+        if let Some(Err(err)) = self.tracer.as_mut().map(|tracer| tracer.trace(cpu)) {
+            self.messages
+                .error(format!("Failed to trace instruction {}", err));
+            self.tracer = None;
+        }
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.before_cycle(cpu);
+        }
+    }
+
+    fn after_frame(&mut self, cpu: &Cpu) {
+        if let Some(Err(err)) = self
+            .recorder
+            .as_mut()
+            .map(|recorder| recorder.capture(&cpu.gpu))
+        {
+            self.messages
+                .error(format!("Failed to record frame {}", err));
+            self.recorder = None;
+        }
+    }
+}
+
+/// Finishes the given recording, if there is one.
+fn stop_recording(recorder: Option<GifRecorder>, messages: &mut Messages) {
+    match recorder.map(GifRecorder::finish) {
+        Some(Ok(path)) => messages.info(format!("Saved recording to {}", path.display())),
+        Some(Err(err)) => messages.error(format!("Failed to save recording {}", err)),
+        None => (),
+    }
+}
 
-        cpu.execute_cycle();
+/// Reports to the user whilst the emulator runs.
+///
+/// The terminal frontend only redraws the parts of the display which
+/// change, so anything printed whilst it's running would be left on top
+/// of it. Messages are held back until the terminal is restored instead.
+struct Messages {
+    /// The messages held back, each along with whether it's an error, or
+    /// `None` if messages are printed straight away.
+    held: Option<Vec<(String, bool)>>,
+}
 
-        // draw only if necessary - otherwise framerate suffers
-        if cpu.gpu.pending_draw {
-            display.render(&mut cpu.gpu);
+impl Messages {
+    /// Creates a new reporter, which holds messages back if asked to.
+    fn new(hold: bool) -> Self {
+        Messages {
+            held: if hold { Some(Vec::new()) } else { None },
         }
+    }
 
-        // sleep to maintain expected clock frequency
-        let duration = start_time.elapsed().unwrap();
-        let remaining_ns: i64 = (tick_duration_ns as i64) - (duration.as_nanos() as i64);
-        if remaining_ns > 0 {
-            std::thread::sleep(Duration::from_nanos(remaining_ns as u64));
+    /// Reports something the user asked for.
+    fn info(&mut self, message: String) {
+        match self.held.as_mut() {
+            Some(held) => held.push((message, false)),
+            None => println!("{}", message),
+        }
+    }
+
+    /// Reports something which went wrong.
+    fn error(&mut self, message: String) {
+        match self.held.as_mut() {
+            Some(held) => held.push((message, true)),
+            None => eprintln!("{}", message),
+        }
+    }
+
+    /// Prints the messages held back, and any from now on straight away.
+    fn release(&mut self) {
+        for (message, error) in self.held.take().unwrap_or_default() {
+            if error {
+                eprintln!("{}", message);
+            } else {
+                println!("{}", message);
+            }
         }
     }
 }
@@ -147,7 +648,7 @@ fn main() {
 fn validate_int(value: &str) -> Result<(), String> {
     value
         .parse::<u32>()
-        .map_err(|err| err.description().to_owned())
+        .map_err(|err| err.to_string())
         .and_then(|val| {
             if val > 0 {
                 Ok(())
@@ -156,17 +657,3 @@ fn validate_int(value: &str) -> Result<(), String> {
             }
         })
 }
-
-/// Returns true if the given string is a positive float.
-fn validate_float(value: &str) -> Result<(), String> {
-    value
-        .parse::<f32>()
-        .map_err(|err| err.description().to_owned())
-        .and_then(|val| {
-            if val > 0.0 {
-                Ok(())
-            } else {
-                Err(String::from("Value must be > 0"))
-            }
-        })
-}
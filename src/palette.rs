@@ -0,0 +1,191 @@
+/// An RGB colour.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    /// Parses a colour from a hex string such as `#FFCC00` or `FFCC00`.
+    #[cfg(feature = "std")]
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let hex = value.trim().trim_start_matches('#');
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("Invalid colour '{}', expected #RRGGBB", value));
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+        Ok(Rgb(channel(0), channel(2), channel(4)))
+    }
+
+    /// Mixes this colour with another. An amount of 0 gives this colour
+    /// and an amount of 1 gives the other.
+    pub fn mix(self, other: Rgb, amount: f32) -> Rgb {
+        let channel = |a: u8, b: u8| (f32::from(a) + (f32::from(b) - f32::from(a)) * amount) as u8;
+        Rgb(
+            channel(self.0, other.0),
+            channel(self.1, other.1),
+            channel(self.2, other.2),
+        )
+    }
+}
+
+/// The colours used to draw the display.
+///
+/// There are four colours so that both planes of an XO-CHIP display can
+/// be drawn:
+/// ```text
+/// Index  Plane 1  Plane 2
+/// 0      off      off      (background)
+/// 1      on       off      (foreground)
+/// 2      off      on
+/// 3      on       on
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Palette {
+    pub colors: [Rgb; 4],
+}
+
+impl Palette {
+    /// Returns the colour for a pixel whose bit in plane 1 is `plane1`
+    /// and in plane 2 is `plane2`.
+    pub fn color(&self, plane1: bool, plane2: bool) -> Rgb {
+        self.colors[plane1 as usize | (plane2 as usize) << 1]
+    }
+
+    /// Parses a palette from either the name of a preset, or a comma
+    /// separated list of two to four hex colours.
+    ///
+    /// When fewer than four colours are given, the missing plane 2 and
+    /// blended colours fall back to the foreground.
+    #[cfg(feature = "std")]
+    pub fn parse(value: &str) -> Result<Self, String> {
+        if let Some((_, palette)) = PRESETS.iter().find(|(name, _)| *name == value) {
+            return Ok(*palette);
+        }
+
+        let colors = value
+            .split(',')
+            .map(Rgb::parse)
+            .collect::<Result<Vec<Rgb>, String>>()?;
+        match colors.len() {
+            2 => Ok(Palette {
+                colors: [colors[0], colors[1], colors[1], colors[1]],
+            }),
+            3 => Ok(Palette {
+                colors: [colors[0], colors[1], colors[2], colors[1]],
+            }),
+            4 => Ok(Palette {
+                colors: [colors[0], colors[1], colors[2], colors[3]],
+            }),
+            _ => Err(format!(
+                "Invalid palette '{}', expected a preset or 2 to 4 colours",
+                value
+            )),
+        }
+    }
+}
+
+/// White on black.
+pub const CLASSIC: Palette = Palette {
+    colors: [
+        Rgb(0x00, 0x00, 0x00),
+        Rgb(0xFF, 0xFF, 0xFF),
+        Rgb(0xAA, 0xAA, 0xAA),
+        Rgb(0x55, 0x55, 0x55),
+    ],
+};
+
+/// Green phosphor monitor.
+pub const GREEN: Palette = Palette {
+    colors: [
+        Rgb(0x00, 0x14, 0x00),
+        Rgb(0x33, 0xFF, 0x33),
+        Rgb(0x1A, 0x8C, 0x1A),
+        Rgb(0x0F, 0x4F, 0x0F),
+    ],
+};
+
+/// Amber phosphor monitor.
+pub const AMBER: Palette = Palette {
+    colors: [
+        Rgb(0x14, 0x0C, 0x00),
+        Rgb(0xFF, 0xB0, 0x00),
+        Rgb(0xB3, 0x7B, 0x00),
+        Rgb(0x5C, 0x3F, 0x00),
+    ],
+};
+
+/// Greenish monochrome LCD.
+pub const LCD: Palette = Palette {
+    colors: [
+        Rgb(0x9B, 0xBC, 0x0F),
+        Rgb(0x0F, 0x38, 0x0F),
+        Rgb(0x30, 0x62, 0x30),
+        Rgb(0x8B, 0xAC, 0x0F),
+    ],
+};
+
+/// The defaults used by the Octo IDE.
+pub const OCTO: Palette = Palette {
+    colors: [
+        Rgb(0x99, 0x66, 0x00),
+        Rgb(0xFF, 0xCC, 0x00),
+        Rgb(0xFF, 0x66, 0x00),
+        Rgb(0x66, 0x22, 0x00),
+    ],
+};
+
+/// All the named palettes.
+pub static PRESETS: &[(&str, Palette)] = &[
+    ("classic", CLASSIC),
+    ("green", GREEN),
+    ("amber", AMBER),
+    ("lcd", LCD),
+    ("octo", OCTO),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_presets_by_name() {
+        for (name, palette) in PRESETS {
+            assert_eq!(Palette::parse(name), Ok(*palette));
+        }
+    }
+
+    #[test]
+    fn parses_colours_with_or_without_a_hash() {
+        assert_eq!(Rgb::parse("#FFCC00"), Ok(Rgb(0xFF, 0xCC, 0x00)));
+        assert_eq!(Rgb::parse("0a1b2c"), Ok(Rgb(0x0A, 0x1B, 0x2C)));
+        assert!(Rgb::parse("#FFF").is_err());
+        assert!(Rgb::parse("#GGGGGG").is_err());
+    }
+
+    #[test]
+    fn fills_missing_colours_with_the_foreground() {
+        let (black, white, red, blue) = (
+            Rgb(0x00, 0x00, 0x00),
+            Rgb(0xFF, 0xFF, 0xFF),
+            Rgb(0xFF, 0x00, 0x00),
+            Rgb(0x00, 0x00, 0xFF),
+        );
+        assert_eq!(
+            Palette::parse("#000000,#FFFFFF").map(|palette| palette.colors),
+            Ok([black, white, white, white])
+        );
+        assert_eq!(
+            Palette::parse("#000000,#FFFFFF,#FF0000").map(|palette| palette.colors),
+            Ok([black, white, red, white])
+        );
+        assert_eq!(
+            Palette::parse("#000000, #FFFFFF, #FF0000, #0000FF").map(|palette| palette.colors),
+            Ok([black, white, red, blue])
+        );
+    }
+
+    #[test]
+    fn rejects_the_wrong_number_of_colours() {
+        assert!(Palette::parse("#000000").is_err());
+        assert!(Palette::parse("#000000,#111111,#222222,#333333,#444444").is_err());
+        assert!(Palette::parse("sepia").is_err());
+    }
+}
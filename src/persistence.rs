@@ -0,0 +1,186 @@
+use crate::gpu::{self, Gpu};
+
+/// How much of a pixel's brightness is kept each frame after it's
+/// switched off, when decaying.
+const DECAY_FACTOR: f32 = 0.6;
+
+/// Brightness below which a decaying pixel is treated as off.
+const DECAY_CUTOFF: f32 = 0.05;
+
+/// The number of pixels on the display.
+const PIXELS: usize = (gpu::WIDTH * gpu::HEIGHT) as usize;
+
+/// How the display fades pixels which have been switched off, to hide
+/// the flicker caused by XOR drawing.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Persistence {
+    /// Pixels are shown exactly as they are in the VRAM.
+    Off,
+
+    /// Pixels fade out over a few frames after being switched off.
+    Decay,
+
+    /// Pixels are shown if they were set in either of the last two frames.
+    Blend,
+}
+
+impl Persistence {
+    /// Parses a persistence mode from its name.
+    #[cfg(feature = "std")]
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "off" => Ok(Persistence::Off),
+            "decay" => Ok(Persistence::Decay),
+            "blend" => Ok(Persistence::Blend),
+            _ => Err(format!(
+                "Invalid persistence '{}', expected off, decay or blend",
+                value
+            )),
+        }
+    }
+}
+
+/// Tracks the brightness of each pixel over recent frames.
+///
+/// This only affects what is shown, and never the VRAM itself.
+pub struct Phosphor {
+    /// The persistence mode.
+    mode: Persistence,
+
+    /// The brightness of each pixel, from 0 (off) to 1 (fully lit).
+    brightness: [f32; PIXELS],
+
+    /// The VRAM as it was on the previous frame.
+    previous: [bool; PIXELS],
+}
+
+impl Phosphor {
+    /// Creates a new phosphor with every pixel off.
+    pub fn new(mode: Persistence) -> Self {
+        Phosphor {
+            mode,
+            brightness: [0.0; PIXELS],
+            previous: [false; PIXELS],
+        }
+    }
+
+    /// Advances the phosphor by one frame, given the current VRAM.
+    pub fn update(&mut self, graphics: &Gpu) {
+        for i in 0..PIXELS {
+            let bit = graphics.pixel(i as u32 % gpu::WIDTH, i as u32 / gpu::WIDTH);
+            self.brightness[i] = match self.mode {
+                Persistence::Off => bit as u8 as f32,
+                Persistence::Blend => (bit || self.previous[i]) as u8 as f32,
+                Persistence::Decay if bit => 1.0,
+                Persistence::Decay => {
+                    let faded = self.brightness[i] * DECAY_FACTOR;
+                    if faded < DECAY_CUTOFF {
+                        0.0
+                    } else {
+                        faded
+                    }
+                }
+            };
+            self.previous[i] = bit;
+        }
+    }
+
+    /// Returns the brightness of the pixel at the given VRAM index.
+    pub fn brightness(&self, index: usize) -> f32 {
+        self.brightness[index]
+    }
+
+    /// Returns true if some pixels are still fading, and so the display
+    /// needs redrawing even if the VRAM hasn't changed.
+    pub fn is_fading(&self) -> bool {
+        match self.mode {
+            Persistence::Off => false,
+            Persistence::Blend => self
+                .brightness
+                .iter()
+                .zip(self.previous.iter())
+                .any(|(&lit, &bit)| lit > 0.0 && !bit),
+            Persistence::Decay => self.brightness.iter().any(|&b| b > 0.0 && b < 1.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu::DrawMode;
+
+    /// Returns a GPU with just the top left pixel set, or a blank one.
+    fn display(lit: bool) -> Gpu {
+        let mut gpu = Gpu::new();
+        if lit {
+            gpu.draw(0, 0, &[0b1000_0000], DrawMode::Wrap);
+        }
+        gpu
+    }
+
+    #[test]
+    fn off_shows_the_vram() {
+        let mut phosphor = Phosphor::new(Persistence::Off);
+        phosphor.update(&display(true));
+        assert_eq!(phosphor.brightness(0), 1.0);
+        assert_eq!(phosphor.brightness(1), 0.0);
+
+        phosphor.update(&display(false));
+        assert_eq!(phosphor.brightness(0), 0.0);
+        assert!(!phosphor.is_fading());
+    }
+
+    #[test]
+    fn decay_fades_once_per_frame_until_the_cutoff() {
+        let mut phosphor = Phosphor::new(Persistence::Decay);
+        phosphor.update(&display(true));
+        assert_eq!(phosphor.brightness(0), 1.0);
+        assert!(!phosphor.is_fading());
+
+        let mut expected = 1.0;
+        for _ in 0..5 {
+            phosphor.update(&display(false));
+            expected *= DECAY_FACTOR;
+            assert_eq!(phosphor.brightness(0), expected);
+            assert!(phosphor.is_fading());
+        }
+
+        // 0.6^6 is below the cutoff
+        phosphor.update(&display(false));
+        assert_eq!(phosphor.brightness(0), 0.0);
+        assert!(!phosphor.is_fading());
+    }
+
+    #[test]
+    fn decaying_pixels_light_up_fully_when_set_again() {
+        let mut phosphor = Phosphor::new(Persistence::Decay);
+        phosphor.update(&display(true));
+        phosphor.update(&display(false));
+        phosphor.update(&display(true));
+        assert_eq!(phosphor.brightness(0), 1.0);
+    }
+
+    #[test]
+    fn blend_shows_pixels_set_in_either_of_the_last_two_frames() {
+        let mut phosphor = Phosphor::new(Persistence::Blend);
+        phosphor.update(&display(true));
+        assert_eq!(phosphor.brightness(0), 1.0);
+
+        phosphor.update(&display(false));
+        assert_eq!(phosphor.brightness(0), 1.0);
+        assert!(phosphor.is_fading());
+
+        phosphor.update(&display(false));
+        assert_eq!(phosphor.brightness(0), 0.0);
+        assert!(!phosphor.is_fading());
+    }
+
+    #[test]
+    fn parses_every_mode() {
+        assert_eq!(Persistence::parse("off"), Ok(Persistence::Off));
+        assert_eq!(Persistence::parse("decay"), Ok(Persistence::Decay));
+        assert_eq!(Persistence::parse("blend"), Ok(Persistence::Blend));
+        assert!(Persistence::parse("glow").is_err());
+    }
+}
@@ -0,0 +1,519 @@
+use crate::cpu::Cpu;
+use crate::disasm::Instruction;
+use crate::runner::Observer;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::ops::RangeInclusive;
+
+/// The number of addresses in memory.
+const MEMORY_SIZE: usize = 4096;
+
+/// The number of entries in each list of the text report.
+const TOP_ENTRIES: usize = 20;
+
+/// A loop, found from a jump or skip which went backwards.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HotLoop {
+    /// The addresses the loop covers, from its first instruction to the
+    /// one which jumps back.
+    pub addresses: RangeInclusive<u16>,
+
+    /// The number of times the loop went back round.
+    pub iterations: u64,
+}
+
+/// The time spent in a subroutine.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Subroutine {
+    /// The number of calls which have returned.
+    pub calls: u64,
+
+    /// The total number of instructions executed between each call and
+    /// its return, including those in any subroutines it calls.
+    pub cycles: u64,
+}
+
+/// Counts how often each address and type of instruction is executed,
+/// along with the loops and subroutines they make up.
+///
+/// The profiler watches the CPU through the run loop, and so only costs
+/// anything when it's in use.
+pub struct Profiler {
+    /// The addresses the program was loaded into.
+    program: RangeInclusive<u16>,
+
+    /// The number of times the instruction at each address was executed.
+    executions: Vec<u64>,
+
+    /// The number of times each type of instruction was executed, keyed
+    /// by its form.
+    forms: BTreeMap<&'static str, u64>,
+
+    /// The number of times each backward jump was taken, keyed by the
+    /// address jumped from and the address jumped to.
+    back_edges: BTreeMap<(u16, u16), u64>,
+
+    /// The time spent in each subroutine, keyed by its address.
+    subroutines: BTreeMap<u16, Subroutine>,
+
+    /// The subroutines which haven't returned yet, with the cycle they
+    /// were called on.
+    calls: Vec<(u16, u64)>,
+
+    /// The address and instruction executed on the previous cycle.
+    previous: Option<(u16, Instruction)>,
+
+    /// The total number of instructions executed.
+    total: u64,
+}
+
+impl Profiler {
+    /// Creates a new profiler for a program of the given length, loaded
+    /// at `0x200`.
+    pub fn new(program_len: usize) -> Self {
+        let end = (0x200 + program_len.max(1) - 1).min(MEMORY_SIZE - 1);
+        Profiler {
+            program: 0x200..=end as u16,
+            executions: vec![0; MEMORY_SIZE],
+            forms: BTreeMap::new(),
+            back_edges: BTreeMap::new(),
+            subroutines: BTreeMap::new(),
+            calls: Vec::new(),
+            previous: None,
+            total: 0,
+        }
+    }
+
+    /// Returns the total number of instructions executed.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Returns the number of times the instruction at the given address
+    /// was executed.
+    pub fn executions(&self, address: u16) -> u64 {
+        self.executions[address as usize]
+    }
+
+    /// Returns the addresses executed most often, most often first.
+    pub fn hot_spots(&self) -> Vec<(u16, u64)> {
+        let mut spots = self
+            .executions
+            .iter()
+            .enumerate()
+            .filter(|&(_, &count)| count > 0)
+            .map(|(address, &count)| (address as u16, count))
+            .collect::<Vec<(u16, u64)>>();
+        spots.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        spots
+    }
+
+    /// Returns the number of times each type of instruction was executed,
+    /// most often first.
+    pub fn forms(&self) -> Vec<(&'static str, u64)> {
+        let mut forms = self
+            .forms
+            .iter()
+            .map(|(&form, &count)| (form, count))
+            .collect::<Vec<(&str, u64)>>();
+        forms.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        forms
+    }
+
+    /// Returns the loops found, most iterations first.
+    pub fn hot_loops(&self) -> Vec<HotLoop> {
+        let mut loops = self
+            .back_edges
+            .iter()
+            .map(|(&(from, to), &iterations)| HotLoop {
+                addresses: to..=from,
+                iterations,
+            })
+            .collect::<Vec<HotLoop>>();
+        loops.sort_by(|a, b| {
+            b.iterations
+                .cmp(&a.iterations)
+                .then(a.addresses.start().cmp(b.addresses.start()))
+        });
+        loops
+    }
+
+    /// Returns the time spent in each subroutine, most cycles first.
+    /// Calls which haven't returned yet aren't counted.
+    pub fn subroutines(&self) -> Vec<(u16, Subroutine)> {
+        let mut subroutines = self
+            .subroutines
+            .iter()
+            .map(|(&address, subroutine)| (address, subroutine.clone()))
+            .collect::<Vec<(u16, Subroutine)>>();
+        subroutines.sort_by(|a, b| b.1.cycles.cmp(&a.1.cycles).then(a.0.cmp(&b.0)));
+        subroutines
+    }
+
+    /// Returns the ranges of the program which were never executed. As
+    /// sprites and other data are never executed either, these aren't
+    /// necessarily dead code.
+    pub fn unexecuted_regions(&self) -> Vec<RangeInclusive<u16>> {
+        // each instruction covers its own address and the one after
+        let mut covered = vec![false; MEMORY_SIZE];
+        for (address, &count) in self.executions.iter().enumerate() {
+            if count > 0 {
+                covered[address] = true;
+                covered[(address + 1) % MEMORY_SIZE] = true;
+            }
+        }
+
+        let mut regions = Vec::new();
+        let mut start = None;
+        for address in self.program.clone() {
+            match (covered[address as usize], start) {
+                (false, None) => start = Some(address),
+                (true, Some(first)) => {
+                    regions.push(first..=address - 1);
+                    start = None;
+                }
+                _ => (),
+            }
+        }
+        if let Some(first) = start {
+            regions.push(first..=*self.program.end());
+        }
+        regions
+    }
+
+    /// Writes the report as text, listing the top entries of each part.
+    pub fn write_text(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "Instructions executed: {}", self.total)?;
+
+        writeln!(out, "\nHot spots:")?;
+        for (address, count) in self.hot_spots().into_iter().take(TOP_ENTRIES) {
+            writeln!(
+                out,
+                "  {:03X}  {:>10}  {}",
+                address,
+                count,
+                self.percent(count)
+            )?;
+        }
+
+        writeln!(out, "\nInstruction types:")?;
+        for (form, count) in self.forms().into_iter().take(TOP_ENTRIES) {
+            writeln!(
+                out,
+                "  {:<13}  {:>10}  {}",
+                form,
+                count,
+                self.percent(count)
+            )?;
+        }
+
+        writeln!(out, "\nHot loops:")?;
+        for hot_loop in self.hot_loops().into_iter().take(TOP_ENTRIES) {
+            writeln!(
+                out,
+                "  {:03X}-{:03X}  {:>10} iterations",
+                hot_loop.addresses.start(),
+                hot_loop.addresses.end(),
+                hot_loop.iterations
+            )?;
+        }
+
+        writeln!(out, "\nSubroutines:")?;
+        for (address, subroutine) in self.subroutines().into_iter().take(TOP_ENTRIES) {
+            writeln!(
+                out,
+                "  {:03X}  {:>10} calls  {:>10} cycles  {}",
+                address,
+                subroutine.calls,
+                subroutine.cycles,
+                self.percent(subroutine.cycles)
+            )?;
+        }
+
+        writeln!(out, "\nUnexecuted regions:")?;
+        for region in self.unexecuted_regions() {
+            writeln!(
+                out,
+                "  {:03X}-{:03X}  {:>4} bytes",
+                region.start(),
+                region.end(),
+                region.end() - region.start() + 1
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Writes the whole report as JSON. Addresses are given as numbers.
+    pub fn write_json(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "{{")?;
+        writeln!(out, "  \"total\": {},", self.total)?;
+
+        let executions = self
+            .hot_spots()
+            .iter()
+            .map(|(address, count)| format!("{{\"address\": {}, \"count\": {}}}", address, count))
+            .collect::<Vec<String>>();
+        write_json_list(out, "executions", &executions, true)?;
+
+        let forms = self
+            .forms()
+            .iter()
+            .map(|(form, count)| format!("{{\"form\": \"{}\", \"count\": {}}}", form, count))
+            .collect::<Vec<String>>();
+        write_json_list(out, "instruction_types", &forms, true)?;
+
+        let loops = self
+            .hot_loops()
+            .iter()
+            .map(|hot_loop| {
+                format!(
+                    "{{\"start\": {}, \"end\": {}, \"iterations\": {}}}",
+                    hot_loop.addresses.start(),
+                    hot_loop.addresses.end(),
+                    hot_loop.iterations
+                )
+            })
+            .collect::<Vec<String>>();
+        write_json_list(out, "hot_loops", &loops, true)?;
+
+        let subroutines = self
+            .subroutines()
+            .iter()
+            .map(|(address, subroutine)| {
+                format!(
+                    "{{\"address\": {}, \"calls\": {}, \"cycles\": {}}}",
+                    address, subroutine.calls, subroutine.cycles
+                )
+            })
+            .collect::<Vec<String>>();
+        write_json_list(out, "subroutines", &subroutines, true)?;
+
+        let regions = self
+            .unexecuted_regions()
+            .iter()
+            .map(|region| {
+                format!(
+                    "{{\"start\": {}, \"end\": {}}}",
+                    region.start(),
+                    region.end()
+                )
+            })
+            .collect::<Vec<String>>();
+        write_json_list(out, "unexecuted_regions", &regions, false)?;
+
+        writeln!(out, "}}")
+    }
+
+    /// Formats the given number of instructions as a percentage of the
+    /// total.
+    fn percent(&self, count: u64) -> String {
+        format!("{:5.1}%", count as f64 * 100.0 / self.total.max(1) as f64)
+    }
+}
+
+impl<R: FnMut() -> u8> Observer<R> for Profiler {
+    fn before_cycle(&mut self, cpu: &Cpu<R>) {
+        let pc = cpu.pc();
+        let cycle = cpu.cycles();
+        let instruction = Instruction::decode(cpu.opcode());
+
+        // anything but a call or return which didn't move forwards is
+        // the end of a loop, including instructions which wait in place
+        if let Some((previous_pc, previous)) = self.previous {
+            let is_call = matches!(previous, Instruction::Call(_) | Instruction::Ret);
+            if !is_call && pc <= previous_pc {
+                *self.back_edges.entry((previous_pc, pc)).or_insert(0) += 1;
+            }
+        }
+
+        match instruction {
+            Instruction::Call(address) => self.calls.push((address, cycle)),
+            Instruction::Ret => {
+                if let Some((address, called)) = self.calls.pop() {
+                    let subroutine = self.subroutines.entry(address).or_default();
+                    subroutine.calls += 1;
+                    subroutine.cycles += cycle + 1 - called;
+                }
+            }
+            _ => (),
+        }
+
+        self.executions[pc as usize % MEMORY_SIZE] += 1;
+        *self.forms.entry(instruction.form()).or_insert(0) += 1;
+        self.previous = Some((pc, instruction));
+        self.total += 1;
+    }
+}
+
+/// Writes a JSON list of already formatted entries, one per line.
+fn write_json_list(
+    out: &mut dyn Write,
+    name: &str,
+    entries: &[String],
+    comma: bool,
+) -> io::Result<()> {
+    writeln!(out, "  \"{}\": [", name)?;
+    for (index, entry) in entries.iter().enumerate() {
+        let separator = if index + 1 < entries.len() { "," } else { "" };
+        writeln!(out, "    {}{}", entry, separator)?;
+    }
+    writeln!(out, "  ]{}", if comma { "," } else { "" })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu::Gpu;
+    use crate::keypad::{Keypad, KeypadSetting};
+    use crate::runner;
+
+    /// A loop which calls a subroutine three times, then stops in a loop
+    /// of its own, followed by two bytes of data.
+    const PROGRAM: [u8; 18] = [
+        0x60, 0x00, // 200: LD V0, 00
+        0x22, 0x0C, // 202: CALL 20C
+        0x70, 0x01, // 204: ADD V0, 01
+        0x30, 0x03, // 206: SE V0, 03
+        0x12, 0x02, // 208: JP 202
+        0x12, 0x0A, // 20A: JP 20A
+        0x61, 0x01, // 20C: LD V1, 01
+        0x00, 0xEE, // 20E: RET
+        0xFF, 0xFF, // 210: data
+    ];
+
+    /// Profiles the given number of instructions of `PROGRAM`, which runs
+    /// the loop three times in the first 18.
+    fn profile(cycles: u32) -> Profiler {
+        let mut cpu = Cpu::with_rng(Gpu::new(), Keypad::new(KeypadSetting::Qwerty), || 0);
+        cpu.load_rom(&PROGRAM).unwrap();
+        let mut profiler = Profiler::new(PROGRAM.len());
+        runner::run_frame(&mut cpu, cycles, &mut profiler);
+        profiler
+    }
+
+    #[test]
+    fn counts_every_execution() {
+        let profiler = profile(20);
+        assert_eq!(profiler.total(), 20);
+        assert_eq!(profiler.executions(0x202), 3);
+        assert_eq!(profiler.executions(0x210), 0);
+        assert_eq!(
+            profiler.hot_spots(),
+            vec![
+                (0x202, 3),
+                (0x204, 3),
+                (0x206, 3),
+                (0x20C, 3),
+                (0x20E, 3),
+                (0x208, 2),
+                (0x20A, 2),
+                (0x200, 1),
+            ]
+        );
+        assert_eq!(
+            profiler.forms(),
+            vec![
+                ("JP addr", 4),
+                ("LD Vx, byte", 4),
+                ("ADD Vx, byte", 3),
+                ("CALL addr", 3),
+                ("RET", 3),
+                ("SE Vx, byte", 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_loops_from_backward_jumps() {
+        // returns go backwards too, but aren't loops
+        assert_eq!(
+            profile(20).hot_loops(),
+            vec![
+                HotLoop {
+                    addresses: 0x202..=0x208,
+                    iterations: 2,
+                },
+                HotLoop {
+                    addresses: 0x20A..=0x20A,
+                    iterations: 1,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn times_subroutines_from_call_to_return() {
+        // each call runs CALL, LD and RET
+        assert_eq!(
+            profile(20).subroutines(),
+            vec![(
+                0x20C,
+                Subroutine {
+                    calls: 3,
+                    cycles: 9,
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn calls_which_havent_returned_arent_counted() {
+        // the third call is still running after 15 instructions
+        assert_eq!(
+            profile(15).subroutines(),
+            vec![(
+                0x20C,
+                Subroutine {
+                    calls: 2,
+                    cycles: 6,
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn finds_unexecuted_regions_of_the_program() {
+        assert_eq!(profile(20).unexecuted_regions(), vec![0x210..=0x211]);
+
+        // after the first call, neither the rest of the loop nor the
+        // return have run
+        assert_eq!(
+            profile(3).unexecuted_regions(),
+            vec![0x204..=0x20B, 0x20E..=0x211]
+        );
+    }
+
+    #[test]
+    fn writes_a_text_report() {
+        let mut out = Vec::new();
+        profile(20).write_text(&mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+
+        assert!(text.starts_with("Instructions executed: 20\n"));
+        assert!(text.contains("\n  202           3   15.0%\n"));
+        assert!(text.contains("\n  JP addr                 4   20.0%\n"));
+        assert!(text.contains("\n  202-208           2 iterations\n"));
+        assert!(text.contains("\n  20C           3 calls           9 cycles   45.0%\n"));
+        assert!(text.ends_with("\nUnexecuted regions:\n  210-211     2 bytes\n"));
+    }
+
+    #[test]
+    fn writes_a_json_report() {
+        let mut out = Vec::new();
+        profile(20).write_json(&mut out).unwrap();
+        let json = String::from_utf8(out).unwrap();
+
+        assert!(json.starts_with("{\n  \"total\": 20,\n  \"executions\": [\n"));
+        assert!(json.contains("\n    {\"address\": 514, \"count\": 3},\n"));
+        assert!(json.contains("\n    {\"form\": \"JP addr\", \"count\": 4},\n"));
+        assert!(json.contains(
+            "\n  \"hot_loops\": [\n    {\"start\": 514, \"end\": 520, \"iterations\": 2},\n"
+        ));
+        assert!(json.contains(
+            "\n  \"subroutines\": [\n    {\"address\": 524, \"calls\": 3, \"cycles\": 9}\n  ],\n"
+        ));
+        assert!(json.ends_with(
+            "\n  \"unexecuted_regions\": [\n    {\"start\": 528, \"end\": 529}\n  ]\n}\n"
+        ));
+    }
+}
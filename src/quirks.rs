@@ -0,0 +1,60 @@
+use crate::gpu::DrawMode;
+
+/// Behaviours which differ between CHIP-8 interpreters.
+///
+/// ROMs are often written against one interpreter and misbehave on
+/// others. The default is what this emulator has always done, which is
+/// what most modern ROMs expect.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quirks {
+    /// `8xy1`, `8xy2` and `8xy3` reset VF to 0 afterwards.
+    pub vf_reset: bool,
+
+    /// `8xy6` and `8xyE` shift Vy and store the result in Vx, rather
+    /// than shifting Vx in place.
+    pub shift_uses_vy: bool,
+
+    /// How `Dxyn` draws sprites which cross the edges of the display.
+    pub draw_mode: DrawMode,
+
+    /// `Dxyn` waits for the start of the next frame before drawing, so
+    /// at most one sprite is drawn per frame.
+    pub display_wait: bool,
+}
+
+impl Quirks {
+    /// The behaviour most modern ROMs expect.
+    pub const MODERN: Quirks = Quirks {
+        vf_reset: false,
+        shift_uses_vy: false,
+        draw_mode: DrawMode::Wrap,
+        display_wait: false,
+    };
+
+    /// The behaviour of the original COSMAC VIP interpreter.
+    pub const COSMAC_VIP: Quirks = Quirks {
+        vf_reset: true,
+        shift_uses_vy: true,
+        draw_mode: DrawMode::Clip,
+        display_wait: true,
+    };
+
+    /// Parses a set of quirks from the name of its interpreter.
+    #[cfg(feature = "std")]
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "modern" => Ok(Quirks::MODERN),
+            "vip" => Ok(Quirks::COSMAC_VIP),
+            _ => Err(format!(
+                "Invalid quirks '{}', expected modern or vip",
+                value
+            )),
+        }
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks::MODERN
+    }
+}
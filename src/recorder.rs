@@ -0,0 +1,261 @@
+use chip8::gpu::{self, Gpu};
+use chip8::palette::Palette;
+use chip8::scheduler::FRAME_RATE;
+use gif::{Encoder, Frame, Repeat};
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+/// The shortest delay, in centiseconds, given to a GIF frame. Most
+/// viewers slow shorter delays right down, so frames which would be
+/// shown for less than this are dropped instead.
+const MIN_DELAY: u64 = 2;
+
+/// Records the display to an animated GIF, one capture per frame.
+///
+/// Frames which are the same as the one before are merged into it by
+/// lengthening its delay, as most frames don't change anything.
+pub struct GifRecorder {
+    /// The GIF encoder.
+    encoder: Encoder<BufWriter<File>>,
+
+    /// The path being written to.
+    path: PathBuf,
+
+    /// The scale each pixel is drawn at.
+    scale: u32,
+
+    /// The width and height of each frame.
+    size: (u16, u16),
+
+    /// The rows of the last captured VRAM, which haven't been written
+    /// yet. Images are only scaled up once they're written.
+    pending: Option<[u64; gpu::HEIGHT as usize]>,
+
+    /// The number of frames captured so far.
+    frames: u64,
+
+    /// The total delay, in centiseconds, written so far.
+    written_delay: u64,
+}
+
+impl GifRecorder {
+    /// Starts recording to the GIF file at the given path. Fails if the
+    /// scale is too large for the frames to fit in a GIF.
+    pub fn create(path: &Path, palette: &Palette, scale: u32) -> Result<Self, String> {
+        let error = |err: &dyn std::fmt::Display| format!("{}: {}", path.display(), err);
+        let scaled = |pixels: u32| {
+            pixels
+                .checked_mul(scale)
+                .and_then(|pixels| u16::try_from(pixels).ok())
+        };
+        let size = scaled(gpu::WIDTH)
+            .zip(scaled(gpu::HEIGHT))
+            .ok_or_else(|| error(&format!("scale {} is too large for a GIF", scale)))?;
+        let file = File::create(path).map_err(|err| error(&err))?;
+        let colors = palette
+            .colors
+            .iter()
+            .flat_map(|color| vec![color.0, color.1, color.2])
+            .collect::<Vec<u8>>();
+        let mut encoder = Encoder::new(BufWriter::new(file), size.0, size.1, &colors)
+            .map_err(|err| error(&err))?;
+        encoder
+            .set_repeat(Repeat::Infinite)
+            .map_err(|err| error(&err))?;
+
+        Ok(GifRecorder {
+            encoder,
+            path: path.to_owned(),
+            scale,
+            size,
+            pending: None,
+            frames: 0,
+            written_delay: 0,
+        })
+    }
+
+    /// Captures the VRAM as the next frame. Should be called once per
+    /// frame, at 60Hz.
+    pub fn capture(&mut self, graphics: &Gpu) -> Result<(), String> {
+        let mut rows = [0; gpu::HEIGHT as usize];
+        for (y, row) in rows.iter_mut().enumerate() {
+            *row = graphics.row(y as u32);
+        }
+
+        if self.pending != Some(rows) {
+            if self.delay_until(self.frames) >= MIN_DELAY {
+                self.write_pending()?;
+            }
+            self.pending = Some(rows);
+        }
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Writes the last frame and finishes the file.
+    pub fn finish(mut self) -> Result<PathBuf, String> {
+        self.write_pending()?;
+        Ok(self.path)
+    }
+
+    /// Returns the delay, in centiseconds, to give the pending image if
+    /// the next one starts at the given frame. This is worked out from
+    /// the total time so far so rounding errors don't build up.
+    fn delay_until(&self, frame: u64) -> u64 {
+        let total = (frame * 100 + u64::from(FRAME_RATE) / 2) / u64::from(FRAME_RATE);
+        total.saturating_sub(self.written_delay)
+    }
+
+    /// Writes the pending image, if there is one, shown for as long as
+    /// it has been pending.
+    fn write_pending(&mut self) -> Result<(), String> {
+        let rows = match self.pending.take() {
+            Some(rows) => rows,
+            None => return Ok(()),
+        };
+
+        // each pixel is a palette index, and the leftmost pixel of a row
+        // is its most significant bit
+        let (width, height) = (u32::from(self.size.0), u32::from(self.size.1));
+        let mut image = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            let row = rows[(y / self.scale) as usize];
+            for x in 0..width {
+                image.push((row >> (gpu::WIDTH - 1 - x / self.scale)) as u8 & 1);
+            }
+        }
+
+        let delay = self
+            .delay_until(self.frames)
+            .max(MIN_DELAY)
+            .min(u64::from(u16::MAX));
+        let frame = Frame {
+            width: self.size.0,
+            height: self.size.1,
+            delay: delay as u16,
+            buffer: Cow::Owned(image),
+            ..Frame::default()
+        };
+        self.encoder
+            .write_frame(&frame)
+            .map_err(|err| format!("{}: {}", self.path.display(), err))?;
+        self.written_delay += delay;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8::gpu::DrawMode;
+    use chip8::palette;
+    use std::env;
+    use std::fs;
+
+    /// Returns a path in the temporary directory for the given test.
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("chip8-{}-{}.gif", name, std::process::id()))
+    }
+
+    /// Records the given displays, one per frame, at the given scale, and
+    /// returns the delay of each frame written along with its pixels.
+    fn record(name: &str, displays: &[&Gpu], scale: u32) -> Vec<(u16, Vec<u8>)> {
+        let path = temp_path(name);
+        let mut recorder = GifRecorder::create(&path, &palette::CLASSIC, scale).unwrap();
+        for graphics in displays {
+            recorder.capture(graphics).unwrap();
+        }
+        recorder.finish().unwrap();
+
+        let mut decoder = gif::DecodeOptions::new()
+            .read_info(File::open(&path).unwrap())
+            .unwrap();
+        let mut frames = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            frames.push((frame.delay, frame.buffer.to_vec()));
+        }
+        fs::remove_file(&path).unwrap();
+        frames
+    }
+
+    /// Returns a display with just the pixel at the given coordinates set.
+    fn display(x: u8, y: u8) -> Gpu {
+        let mut gpu = Gpu::new();
+        gpu.draw(x, y, &[0b1000_0000], DrawMode::Wrap);
+        gpu
+    }
+
+    #[test]
+    fn frames_are_scaled_up() {
+        let frames = record("scaled", &[&display(1, 0)], 2);
+        assert_eq!(frames.len(), 1);
+
+        let image = &frames[0].1;
+        let width = (gpu::WIDTH * 2) as usize;
+        assert_eq!(image.len(), width * (gpu::HEIGHT * 2) as usize);
+        let lit = (0..image.len())
+            .filter(|&i| image[i] == 1)
+            .map(|i| (i % width, i / width))
+            .collect::<Vec<_>>();
+        assert_eq!(lit, vec![(2, 0), (3, 0), (2, 1), (3, 1)]);
+    }
+
+    #[test]
+    fn unchanged_frames_are_merged() {
+        let (first, second) = (display(0, 0), display(1, 0));
+        let mut displays = vec![&first; 60];
+        displays.extend(vec![&second; 30]);
+
+        let delays = record("unchanged", &displays, 1)
+            .into_iter()
+            .map(|(delay, _)| delay)
+            .collect::<Vec<_>>();
+        assert_eq!(delays, vec![100, 50]);
+    }
+
+    #[test]
+    fn frames_shorter_than_the_minimum_delay_are_dropped() {
+        // a change every frame would be shown for under 2 centiseconds
+        let (first, second) = (display(0, 0), display(1, 0));
+        let displays = (0..60)
+            .map(|frame| if frame % 2 == 0 { &first } else { &second })
+            .collect::<Vec<_>>();
+
+        let delays = record("short", &displays, 1)
+            .into_iter()
+            .map(|(delay, _)| u64::from(delay))
+            .collect::<Vec<_>>();
+        assert!(delays.len() < 60);
+        assert!(delays.iter().all(|&delay| delay >= MIN_DELAY));
+
+        // the delays still add up to a second
+        assert_eq!(delays.iter().sum::<u64>(), 100);
+    }
+
+    #[test]
+    fn delays_are_rounded_from_the_total_time() {
+        let path = temp_path("delays");
+        let mut recorder = GifRecorder::create(&path, &palette::CLASSIC, 1).unwrap();
+        assert_eq!(recorder.delay_until(0), 0);
+        assert_eq!(recorder.delay_until(1), 2);
+        assert_eq!(recorder.delay_until(60), 100);
+
+        // what's been written already is taken off
+        recorder.written_delay = 2;
+        assert_eq!(recorder.delay_until(2), 1);
+        assert_eq!(recorder.delay_until(3), 3);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn scales_too_large_for_a_gif_are_rejected() {
+        let path = temp_path("too-large");
+        assert!(GifRecorder::create(&path, &palette::CLASSIC, 1023).is_ok());
+        assert!(GifRecorder::create(&path, &palette::CLASSIC, 1024).is_err());
+        assert!(GifRecorder::create(&path, &palette::CLASSIC, u32::MAX).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,3 +1,7 @@
+use crate::palette::Palette;
+use crate::persistence::Persistence;
+use crate::quirks::Quirks;
+
 /// Represents a ROM.
 pub struct Rom<'a> {
     /// The name of the ROM.
@@ -5,6 +9,15 @@ pub struct Rom<'a> {
 
     /// The data of the ROM.
     pub data: &'a [u8],
+
+    /// The palette the ROM is best shown with, if it has one.
+    pub palette: Option<Palette>,
+
+    /// The persistence the ROM is best shown with, if it has one.
+    pub persistence: Option<Persistence>,
+
+    /// The quirks the ROM expects, if it needs any.
+    pub quirks: Option<Quirks>,
 }
 
 /// All the available ROMs.
@@ -12,30 +25,55 @@ pub static ROMS: &[Rom] = &[
     Rom {
         name: "pong",
         data: &PONG,
+        palette: None,
+        persistence: Some(Persistence::Decay),
+        quirks: None,
     },
     Rom {
         name: "bon",
         data: &BON,
+        palette: None,
+        persistence: None,
+        quirks: None,
     },
     Rom {
         name: "walk",
         data: &WALK,
+        palette: None,
+        persistence: None,
+        quirks: None,
     },
     Rom {
         name: "particle",
         data: &PARTICLE,
+        palette: None,
+        persistence: None,
+        quirks: None,
     },
     Rom {
         name: "puzzle",
         data: &PUZZLE_15,
+        palette: None,
+        persistence: None,
+        quirks: None,
     },
     Rom {
         name: "space-invaders",
         data: &SPACE_INVADERS,
+        palette: None,
+        persistence: Some(Persistence::Decay),
+        // written for the VIP, where drawing paced the game
+        quirks: Some(Quirks {
+            display_wait: true,
+            ..Quirks::MODERN
+        }),
     },
     Rom {
         name: "keypad-test",
         data: &KEYPAD_TEST,
+        palette: None,
+        persistence: None,
+        quirks: None,
     },
 ];
 
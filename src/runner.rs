@@ -0,0 +1,421 @@
+use crate::cpu::Cpu;
+use crate::frontend::{AudioSink, Command, InputSource, VideoSink};
+use crate::scheduler::{self, FrameScheduler};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often input is polled whilst paused.
+const PAUSED_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The run loop shared by every frontend.
+///
+/// Each frame runs a fixed number of instructions and ticks the timers,
+/// after which the display is redrawn if needed.
+pub struct Runner {
+    /// Where the display is drawn.
+    video: Box<dyn VideoSink>,
+
+    /// Where the tone is played.
+    audio: Box<dyn AudioSink>,
+
+    /// Where key presses come from.
+    input: Box<dyn InputSource>,
+
+    /// Paces the frames.
+    scheduler: FrameScheduler,
+
+    /// The number of instructions run per frame.
+    instructions_per_frame: u32,
+
+    /// The speed multiplier to run at when not fast-forwarding or running
+    /// uncapped.
+    speed: f32,
+
+    /// Whether frames are run as fast as possible.
+    unlimited: bool,
+
+    /// The speed multiplier to fast-forward at, or `None` to fast-forward
+    /// as fast as possible.
+    fast_forward_speed: Option<f32>,
+
+    /// Whether the fast-forward command is being held.
+    fast_forwarding: bool,
+
+    /// Whether frames are only run when advanced one at a time.
+    paused: bool,
+
+    /// The number of frames to run whilst paused.
+    frames_to_advance: u32,
+
+    /// Whether the display needs drawing again as it was last shown,
+    /// e.g. because the window was resized, rather than because the VRAM
+    /// has changed.
+    repaint: bool,
+}
+
+impl Runner {
+    /// Creates a new run loop with the given frontend.
+    pub fn new(
+        video: Box<dyn VideoSink>,
+        audio: Box<dyn AudioSink>,
+        input: Box<dyn InputSource>,
+        scheduler: FrameScheduler,
+        instructions_per_frame: u32,
+    ) -> Self {
+        let mut runner = Runner {
+            video,
+            audio,
+            input,
+            speed: scheduler.speed().unwrap_or(1.0),
+            unlimited: scheduler.speed().is_none(),
+            scheduler,
+            instructions_per_frame,
+            fast_forward_speed: None,
+            fast_forwarding: false,
+            paused: false,
+            frames_to_advance: 0,
+            repaint: false,
+        };
+        runner.video.show_speed(runner.scheduler.speed());
+        runner
+    }
+
+    /// Sets the speed multiplier to fast-forward at, or `None` to
+    /// fast-forward as fast as possible, which is the default.
+    pub fn set_fast_forward_speed(&mut self, speed: Option<f32>) {
+        self.fast_forward_speed = speed;
+        self.update_speed();
+    }
+
+    /// Runs frames until the user gives a command, which is returned so
+    /// the caller can act on it. `observer` is told about every
+    /// instruction and frame.
+    ///
+    /// Redraw, speed, pause and frame advance commands are handled here
+    /// rather than returned.
+    pub fn run<R: FnMut() -> u8>(
+        &mut self,
+        cpu: &mut Cpu<R>,
+        observer: &mut dyn Observer<R>,
+    ) -> Command {
+        loop {
+            match self.input.poll(&mut cpu.keypad) {
+                Some(Command::Redraw) => {
+                    cpu.gpu.mark_dirty();
+                    self.repaint = true;
+                }
+                Some(Command::FastForward(held)) => {
+                    self.fast_forwarding = held;
+                    self.update_speed();
+                }
+                Some(Command::ToggleUnlimitedSpeed) => {
+                    self.unlimited = !self.unlimited;
+                    self.update_speed();
+                }
+                Some(Command::SpeedUp) => {
+                    self.speed = (self.speed * 2.0).min(scheduler::MAX_SPEED);
+                    self.unlimited = false;
+                    self.update_speed();
+                }
+                Some(Command::SpeedDown) => {
+                    self.speed = (self.speed / 2.0).max(scheduler::MIN_SPEED);
+                    self.unlimited = false;
+                    self.update_speed();
+                }
+                Some(Command::TogglePause) => {
+                    self.paused = !self.paused;
+                    self.frames_to_advance = 0;
+                    self.video.show_paused(self.paused);
+                }
+                Some(Command::AdvanceFrame) if self.paused => self.frames_to_advance += 1,
+                Some(Command::AdvanceFrame) => (),
+                Some(command) => return command,
+                None => (),
+            }
+
+            // keep to the schedule whilst paused, so that resuming doesn't
+            // try to catch up
+            let due = self.scheduler.frames_due(Instant::now());
+            let frames = if self.paused {
+                std::mem::take(&mut self.frames_to_advance)
+            } else {
+                due
+            };
+            for _ in 0..frames {
+                run_frame(cpu, self.instructions_per_frame, observer);
+                self.video.end_frame(&cpu.gpu);
+                self.input.end_frame(&mut cpu.keypad);
+                observer.after_frame(cpu);
+            }
+            self.audio.set_tone(cpu.is_sound_playing() && !self.paused);
+
+            // draw only if necessary - otherwise framerate suffers. Whilst
+            // paused, the display is still redrawn after a reset, which the
+            // sink is told of as a frame so that it shows the VRAM as it is
+            // now, or when the window needs it, which just draws the display
+            // again as it was
+            let fading = frames > 0 && self.video.is_fading();
+            let dirty = (frames > 0 || self.paused) && cpu.gpu.is_dirty();
+            if frames == 0 && dirty && !self.repaint {
+                self.video.end_frame(&cpu.gpu);
+            }
+            if dirty || fading {
+                self.video.render(&cpu.gpu);
+                cpu.gpu.clear_dirty();
+                self.repaint = false;
+            }
+
+            if self.paused {
+                thread::sleep(PAUSED_POLL_INTERVAL);
+            } else {
+                self.scheduler.sleep_until_next_frame();
+            }
+        }
+    }
+
+    /// Paces the frames at the speed chosen by the speed commands, and
+    /// shows it if it's changed.
+    fn update_speed(&mut self) {
+        let speed = if self.fast_forwarding {
+            self.fast_forward_speed
+        } else if self.unlimited {
+            None
+        } else {
+            Some(self.speed)
+        };
+        if speed != self.scheduler.speed() {
+            self.scheduler.set_speed(speed);
+            self.video.show_speed(speed);
+        }
+    }
+}
+
+/// Watches the CPU as it runs, e.g. to trace or record it.
+pub trait Observer<R = fn() -> u8> {
+    /// Called before each instruction is executed.
+    fn before_cycle(&mut self, _cpu: &Cpu<R>) {}
+
+    /// Called after each frame, once the timers have ticked.
+    fn after_frame(&mut self, _cpu: &Cpu<R>) {}
+}
+
+/// Watches nothing.
+impl<R> Observer<R> for () {}
+
+/// Runs a single frame: the given number of instructions, followed by
+/// a tick of the timers. The frame ends early if the CPU is waiting for
+/// the next one to draw.
+pub fn run_frame<R: FnMut() -> u8>(
+    cpu: &mut Cpu<R>,
+    instructions_per_frame: u32,
+    observer: &mut dyn Observer<R>,
+) {
+    for _ in 0..instructions_per_frame {
+        observer.before_cycle(cpu);
+        cpu.execute_cycle();
+        if cpu.is_waiting_for_vblank() {
+            break;
+        }
+    }
+    cpu.tick_timers();
+    cpu.keypad.clear_edges();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::{NullAudio, NullVideo};
+    use crate::gpu::Gpu;
+    use crate::keypad::{Keypad, KeypadSetting};
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    /// Input which gives each of the commands in turn, then quits.
+    struct Script(Vec<Command>);
+
+    impl InputSource for Script {
+        fn poll(&mut self, _keypad: &mut Keypad) -> Option<Command> {
+            Some(if self.0.is_empty() {
+                Command::Quit
+            } else {
+                self.0.remove(0)
+            })
+        }
+    }
+
+    /// Returns the speed the scheduler is left at after running the
+    /// given commands, starting at normal speed.
+    fn speed_after(commands: &[Command]) -> Option<f32> {
+        let mut cpu = Cpu::with_rng(Gpu::new(), Keypad::new(KeypadSetting::Qwerty), || 0);
+        let mut runner = Runner::new(
+            Box::new(NullVideo),
+            Box::new(NullAudio),
+            Box::new(Script(commands.to_vec())),
+            FrameScheduler::new(1.0),
+            1,
+        );
+        runner.set_fast_forward_speed(Some(4.0));
+        runner.run(&mut cpu, &mut ());
+        runner.scheduler.speed()
+    }
+
+    #[test]
+    fn speed_commands_change_the_schedule() {
+        assert_eq!(speed_after(&[]), Some(1.0));
+        assert_eq!(speed_after(&[Command::SpeedUp]), Some(2.0));
+        assert_eq!(
+            speed_after(&[Command::SpeedDown, Command::SpeedDown]),
+            Some(0.25)
+        );
+        assert_eq!(
+            speed_after(&[Command::SpeedUp; 10]),
+            Some(scheduler::MAX_SPEED)
+        );
+        assert_eq!(speed_after(&[Command::ToggleUnlimitedSpeed]), None);
+        assert_eq!(
+            speed_after(&[Command::ToggleUnlimitedSpeed, Command::ToggleUnlimitedSpeed]),
+            Some(1.0)
+        );
+        assert_eq!(
+            speed_after(&[Command::ToggleUnlimitedSpeed, Command::SpeedUp]),
+            Some(2.0)
+        );
+    }
+
+    /// Returns the number of frames run by the given commands, which
+    /// start paused.
+    fn frames_run_when_paused(commands: &[Command]) -> u64 {
+        let mut commands = commands.to_vec();
+        commands.insert(0, Command::TogglePause);
+
+        // JP 0x200
+        let mut cpu = Cpu::with_rng(Gpu::new(), Keypad::new(KeypadSetting::Qwerty), || 0);
+        cpu.load_rom(&[0x12, 0x00]).unwrap();
+        let mut runner = Runner::new(
+            Box::new(NullVideo),
+            Box::new(NullAudio),
+            Box::new(Script(commands)),
+            FrameScheduler::uncapped(),
+            1,
+        );
+        runner.run(&mut cpu, &mut ());
+        cpu.cycles()
+    }
+
+    #[test]
+    fn frames_only_advance_one_at_a_time_whilst_paused() {
+        assert_eq!(frames_run_when_paused(&[]), 0);
+        assert_eq!(frames_run_when_paused(&[Command::AdvanceFrame]), 1);
+        assert_eq!(
+            frames_run_when_paused(&[Command::AdvanceFrame, Command::AdvanceFrame]),
+            2
+        );
+
+        // resuming runs a frame for every poll
+        assert_eq!(
+            frames_run_when_paused(&[Command::TogglePause, Command::Redraw]),
+            2
+        );
+    }
+
+    /// A display which counts the frames it's told of and the times it's
+    /// rendered.
+    struct Counter {
+        frames: Rc<Cell<u32>>,
+        renders: Rc<Cell<u32>>,
+    }
+
+    impl VideoSink for Counter {
+        fn render(&mut self, _graphics: &Gpu) {
+            self.renders.set(self.renders.get() + 1);
+        }
+
+        fn end_frame(&mut self, _graphics: &Gpu) {
+            self.frames.set(self.frames.get() + 1);
+        }
+    }
+
+    /// Returns the number of frames the display is told of, and the
+    /// number of times it's rendered, by the given commands, which start
+    /// paused. The VRAM starts out changed if `reset` is true, as it is
+    /// after a reset.
+    fn frames_shown_when_paused(commands: &[Command], reset: bool) -> (u32, u32) {
+        let mut commands = commands.to_vec();
+        commands.insert(0, Command::TogglePause);
+
+        let (frames, renders) = (Rc::new(Cell::new(0)), Rc::new(Cell::new(0)));
+        let mut cpu = Cpu::with_rng(Gpu::new(), Keypad::new(KeypadSetting::Qwerty), || 0);
+        cpu.load_rom(&[0x12, 0x00]).unwrap();
+        cpu.gpu.clear_dirty();
+        if reset {
+            cpu.gpu.mark_dirty();
+        }
+        let mut runner = Runner::new(
+            Box::new(Counter {
+                frames: frames.clone(),
+                renders: renders.clone(),
+            }),
+            Box::new(NullAudio),
+            Box::new(Script(commands)),
+            FrameScheduler::uncapped(),
+            1,
+        );
+        runner.run(&mut cpu, &mut ());
+        (frames.get(), renders.get())
+    }
+
+    #[test]
+    fn redrawing_whilst_paused_repaints_without_a_frame() {
+        assert_eq!(frames_shown_when_paused(&[], false), (0, 0));
+        assert_eq!(frames_shown_when_paused(&[Command::Redraw], false), (0, 1));
+        assert_eq!(
+            frames_shown_when_paused(&[Command::Redraw, Command::Redraw, Command::Redraw], false),
+            (0, 3)
+        );
+
+        // the VRAM changing whilst paused, or advancing a frame, are still
+        // shown as frames
+        assert_eq!(frames_shown_when_paused(&[], true), (1, 1));
+        assert_eq!(
+            frames_shown_when_paused(&[Command::Redraw, Command::AdvanceFrame], false),
+            (1, 1)
+        );
+    }
+
+    #[test]
+    fn fast_forwarding_lasts_while_held() {
+        assert_eq!(speed_after(&[Command::FastForward(true)]), Some(4.0));
+        assert_eq!(
+            speed_after(&[Command::FastForward(true), Command::FastForward(false)]),
+            Some(1.0)
+        );
+
+        // changes made whilst fast-forwarding apply once it's released
+        assert_eq!(
+            speed_after(&[
+                Command::FastForward(true),
+                Command::SpeedUp,
+                Command::FastForward(false)
+            ]),
+            Some(2.0)
+        );
+    }
+
+    #[test]
+    fn frames_end_early_when_waiting_to_draw() {
+        // DRW V0, V0, 1; JP 0x200
+        let mut cpu = Cpu::with_rng(Gpu::new(), Keypad::new(KeypadSetting::Qwerty), || 0);
+        cpu.load_rom(&[0xD0, 0x01, 0x12, 0x00]).unwrap();
+        cpu.quirks.display_wait = true;
+
+        // the first draw waits for the end of the first frame
+        run_frame(&mut cpu, 100, &mut ());
+        assert_eq!(cpu.cycles(), 1);
+
+        // then each frame is a draw and a jump, and a stall on the next
+        for frame in 1..=3 {
+            run_frame(&mut cpu, 100, &mut ());
+            assert_eq!(cpu.cycles(), 1 + frame * 3);
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use std::time::{Duration, Instant};
+
+/// The number of frames per second. The timers count down, and the
+/// display is refreshed, once per frame.
+pub const FRAME_RATE: u32 = 60;
+
+/// The most frames which will be run back to back when the host has
+/// fallen behind. Anything beyond this is dropped rather than caught up.
+pub const MAX_CATCH_UP_FRAMES: u32 = 4;
+
+/// The slowest speed multiplier frames can be run at.
+pub const MIN_SPEED: f32 = 0.125;
+
+/// The fastest speed multiplier frames can be run at, short of running
+/// uncapped.
+pub const MAX_SPEED: f32 = 16.0;
+
+/// Paces the emulator at a fixed frame rate using a monotonic clock.
+pub struct FrameScheduler {
+    /// The speed multiplier, or `None` if frames are run as fast as
+    /// possible.
+    speed: Option<f32>,
+
+    /// The length of a single frame, or `None` if frames are run as fast
+    /// as possible.
+    frame_duration: Option<Duration>,
+
+    /// The time at which the next frame is due.
+    next_frame: Instant,
+}
+
+impl FrameScheduler {
+    /// Creates a new scheduler whose first frame is due immediately.
+    ///
+    /// The speed multiplier scales the frame rate, so a value of `2`
+    /// runs frames twice as often.
+    pub fn new(speed_multiplier: f32) -> Self {
+        FrameScheduler {
+            speed: Some(speed_multiplier),
+            frame_duration: Some(frame_duration(speed_multiplier)),
+            next_frame: Instant::now(),
+        }
+    }
+
+    /// Creates a new scheduler which runs frames as fast as possible.
+    pub fn uncapped() -> Self {
+        FrameScheduler {
+            speed: None,
+            frame_duration: None,
+            next_frame: Instant::now(),
+        }
+    }
+
+    /// Returns the speed multiplier frames are run at, or `None` if
+    /// they're run as fast as possible.
+    pub fn speed(&self) -> Option<f32> {
+        self.speed
+    }
+
+    /// Changes the speed multiplier frames are run at, or runs them as
+    /// fast as possible if `None`. The next frame is due immediately.
+    pub fn set_speed(&mut self, speed_multiplier: Option<f32>) {
+        self.speed = speed_multiplier;
+        self.frame_duration = speed_multiplier.map(frame_duration);
+        self.next_frame = Instant::now();
+    }
+
+    /// Returns the number of frames which should be run at the given
+    /// time, which is usually `Instant::now()`.
+    ///
+    /// This is usually 0 or 1, but can be up to `MAX_CATCH_UP_FRAMES`
+    /// if the host has fallen behind. If it has fallen further behind
+    /// than that, the schedule is restarted from now.
+    pub fn frames_due(&mut self, now: Instant) -> u32 {
+        let frame_duration = match self.frame_duration {
+            Some(frame_duration) => frame_duration,
+            None => return 1,
+        };
+
+        if now < self.next_frame {
+            return 0;
+        }
+
+        let missed = (now - self.next_frame).as_nanos() / frame_duration.as_nanos();
+        if missed >= u128::from(MAX_CATCH_UP_FRAMES) {
+            self.next_frame = now + frame_duration;
+            MAX_CATCH_UP_FRAMES
+        } else {
+            let due = missed as u32 + 1;
+            self.next_frame += frame_duration * due;
+            due
+        }
+    }
+
+    /// Sleeps until the next frame is due.
+    pub fn sleep_until_next_frame(&self) {
+        if self.frame_duration.is_none() {
+            return;
+        }
+        let now = Instant::now();
+        if self.next_frame > now {
+            std::thread::sleep(self.next_frame - now);
+        }
+    }
+}
+
+/// Returns the length of a frame at the given speed multiplier.
+fn frame_duration(speed_multiplier: f32) -> Duration {
+    let frame_ns = 1_000_000_000.0 / (FRAME_RATE as f32 * speed_multiplier);
+    Duration::from_nanos((frame_ns as u64).max(1))
+}
+
+/// Parses a speed multiplier, which must be between `MIN_SPEED` and
+/// `MAX_SPEED`.
+pub fn parse_multiplier(value: &str) -> Result<f32, String> {
+    value
+        .parse()
+        .ok()
+        .filter(|speed| (MIN_SPEED..=MAX_SPEED).contains(speed))
+        .ok_or_else(|| {
+            format!(
+                "Invalid speed '{}', expected {} to {}",
+                value, MIN_SPEED, MAX_SPEED
+            )
+        })
+}
+
+/// Parses a speed multiplier like `parse_multiplier`, or `unlimited` to
+/// run as fast as possible.
+pub fn parse_speed(value: &str) -> Result<Option<f32>, String> {
+    if value == "unlimited" {
+        Ok(None)
+    } else {
+        parse_multiplier(value).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_fastest_speed_still_has_a_frame_duration() {
+        let mut scheduler = FrameScheduler::new(MAX_SPEED);
+        assert!(scheduler.frames_due(Instant::now()) > 0);
+
+        // speeds beyond the limit are rejected when parsed, but still
+        // don't divide by zero
+        assert_eq!(frame_duration(1e10), Duration::from_nanos(1));
+        FrameScheduler::new(1e10).frames_due(Instant::now());
+    }
+
+    #[test]
+    fn frames_on_time_are_run_one_at_a_time() {
+        let mut scheduler = FrameScheduler::new(1.0);
+        let start = scheduler.next_frame;
+        let frame = frame_duration(1.0);
+
+        assert_eq!(scheduler.frames_due(start), 1);
+        assert_eq!(scheduler.frames_due(start + frame / 2), 0);
+        assert_eq!(scheduler.frames_due(start + frame), 1);
+        assert_eq!(scheduler.next_frame, start + frame * 2);
+    }
+
+    #[test]
+    fn late_frames_are_caught_up() {
+        let mut scheduler = FrameScheduler::new(1.0);
+        let start = scheduler.next_frame;
+        let frame = frame_duration(1.0);
+
+        // one frame late runs the missed frame along with this one
+        assert_eq!(scheduler.frames_due(start + frame), 2);
+        assert_eq!(scheduler.next_frame, start + frame * 2);
+
+        // several frames late runs them all, up to the limit
+        assert_eq!(scheduler.frames_due(start + frame * 4), 3);
+        assert_eq!(scheduler.next_frame, start + frame * 5);
+        assert_eq!(scheduler.frames_due(start + frame * 8), MAX_CATCH_UP_FRAMES);
+        assert_eq!(scheduler.next_frame, start + frame * 9);
+    }
+
+    #[test]
+    fn falling_far_behind_restarts_the_schedule() {
+        let mut scheduler = FrameScheduler::new(1.0);
+        let start = scheduler.next_frame;
+        let frame = frame_duration(1.0);
+
+        let now = start + frame * 100;
+        assert_eq!(scheduler.frames_due(now), MAX_CATCH_UP_FRAMES);
+        assert_eq!(scheduler.next_frame, now + frame);
+        assert_eq!(scheduler.frames_due(now + frame / 2), 0);
+        assert_eq!(scheduler.frames_due(now + frame), 1);
+    }
+
+    #[test]
+    fn uncapped_frames_are_always_due() {
+        let mut scheduler = FrameScheduler::uncapped();
+        assert_eq!(scheduler.frames_due(Instant::now()), 1);
+        assert_eq!(scheduler.frames_due(Instant::now()), 1);
+    }
+
+    #[test]
+    fn speeds_are_limited_when_parsed() {
+        assert_eq!(parse_multiplier("1"), Ok(1.0));
+        assert_eq!(parse_multiplier("16"), Ok(MAX_SPEED));
+        assert_eq!(parse_multiplier("0.125"), Ok(MIN_SPEED));
+        assert!(parse_multiplier("16.5").is_err());
+        assert!(parse_multiplier("0.1").is_err());
+        assert!(parse_multiplier("1e10").is_err());
+        assert!(parse_multiplier("inf").is_err());
+        assert!(parse_multiplier("NaN").is_err());
+        assert!(parse_multiplier("fast").is_err());
+
+        assert_eq!(parse_speed("unlimited"), Ok(None));
+        assert_eq!(parse_speed("2"), Ok(Some(2.0)));
+        assert!(parse_speed("inf").is_err());
+    }
+}
@@ -0,0 +1,96 @@
+use chip8::gpu::Image;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The largest scale screenshots and recordings can be saved at. This
+/// keeps images to tens of megabytes, and well within the largest size a
+/// GIF can be.
+pub const MAX_SCALE: u32 = 64;
+
+/// Writes the given image to a PNG file.
+pub fn save_png(image: &Image, path: &Path) -> Result<(), String> {
+    let file = File::create(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), image.width, image.height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&image.pixels))
+        .map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+/// Saves the given image into the directory as a PNG named after the ROM
+/// and the current time, creating the directory if needed. Returns the
+/// path written to.
+pub fn save_screenshot(image: &Image, dir: &Path, rom_name: &str) -> Result<PathBuf, String> {
+    let path = output_path(dir, rom_name, "png")?;
+    save_png(image, &path)?;
+    Ok(path)
+}
+
+/// Returns the path of a file in the directory named after the ROM and
+/// the current time, with the given extension, creating the directory if
+/// needed.
+pub fn output_path(dir: &Path, rom_name: &str, extension: &str) -> Result<PathBuf, String> {
+    fs::create_dir_all(dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+    Ok(dir.join(format!("{}-{}.{}", rom_name, timestamp(), extension)))
+}
+
+/// Returns the number of milliseconds since the Unix epoch, for use in
+/// file names.
+pub fn timestamp() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis())
+        .unwrap_or_default()
+}
+
+/// Parses the scale to save screenshots and recordings at, which must be
+/// between 1 and `MAX_SCALE`.
+pub fn parse_scale(value: &str) -> Result<u32, String> {
+    value
+        .parse()
+        .ok()
+        .filter(|scale| (1..=MAX_SCALE).contains(scale))
+        .ok_or_else(|| format!("Invalid scale '{}', expected 1 to {}", value, MAX_SCALE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn output_paths_are_named_after_the_rom_and_time() {
+        let dir = env::temp_dir()
+            .join(format!("chip8-screenshots-{}", std::process::id()))
+            .join("nested");
+        let before = timestamp();
+        let path = output_path(&dir, "pong", "png").unwrap();
+        let after = timestamp();
+        assert!(dir.is_dir());
+        fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+
+        assert_eq!(path.parent(), Some(dir.as_path()));
+        let name = path.file_name().unwrap().to_str().unwrap();
+        let time: u128 = name
+            .strip_prefix("pong-")
+            .and_then(|name| name.strip_suffix(".png"))
+            .and_then(|time| time.parse().ok())
+            .unwrap_or_else(|| panic!("Unexpected name {}", name));
+        assert!((before..=after).contains(&time));
+    }
+
+    #[test]
+    fn scales_are_limited_when_parsed() {
+        assert_eq!(parse_scale("1"), Ok(1));
+        assert_eq!(parse_scale("64"), Ok(MAX_SCALE));
+        assert!(parse_scale("0").is_err());
+        assert!(parse_scale("65").is_err());
+        assert!(parse_scale("1024").is_err());
+        assert!(parse_scale("-1").is_err());
+        assert!(parse_scale("big").is_err());
+    }
+}
@@ -0,0 +1,327 @@
+use chip8::frontend::{AudioSink, Command, InputSource, VideoSink};
+use chip8::gpu;
+use chip8::gpu::Gpu;
+use chip8::keypad::Keypad;
+use chip8::palette::Palette;
+use chip8::persistence::{Persistence, Phosphor};
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+use sdl2::render::{Canvas, Texture};
+use sdl2::video::Window;
+use sdl2::EventPump;
+
+/// The number of bytes per pixel in the streaming texture.
+const BYTES_PER_PIXEL: usize = 3;
+
+/// The frequency of the tone, in Hz.
+const TONE_FREQUENCY: f32 = 440.0;
+
+/// The volume of the tone, from 0 to 1.
+const TONE_VOLUME: f32 = 0.25;
+
+/// Represents the drawn display.
+pub struct Display {
+    /// The canvas.
+    canvas: Canvas<Window>,
+
+    /// The streaming texture the VRAM is uploaded into every frame.
+    ///
+    /// This is at the native resolution and scaled up by SDL when it's
+    /// copied onto the canvas.
+    texture: Texture,
+
+    /// The colours to draw with.
+    palette: Palette,
+
+    /// The brightness of each pixel over recent frames.
+    phosphor: Phosphor,
+
+    /// The speed shown in the title, or `None` if unlimited.
+    speed: Option<f32>,
+
+    /// Whether the title shows that the emulator is paused.
+    paused: bool,
+}
+
+impl Display {
+    /// Creates a new display instance
+    pub fn new(
+        sdl_context: &sdl2::Sdl,
+        scale: u32,
+        palette: Palette,
+        persistence: Persistence,
+    ) -> Self {
+        let video_subsystem = sdl_context.video().expect("No SDL video context found");
+
+        let window = video_subsystem
+            .window("Chip8", gpu::WIDTH * scale, gpu::HEIGHT * scale)
+            .position_centered()
+            .resizable()
+            .opengl()
+            .build()
+            .expect("Failed to build window");
+
+        let mut canvas = window
+            .into_canvas()
+            .build()
+            .expect("Failed to build canvas");
+        canvas.clear();
+        canvas.present();
+
+        let texture = canvas
+            .create_texture_streaming(PixelFormatEnum::RGB24, gpu::WIDTH, gpu::HEIGHT)
+            .expect("Failed to build texture");
+
+        Display {
+            canvas,
+            texture,
+            palette,
+            phosphor: Phosphor::new(persistence),
+            speed: Some(1.0),
+            paused: false,
+        }
+    }
+
+    /// Returns the area of the window to draw the display into.
+    ///
+    /// This is the largest integer multiple of the native resolution which
+    /// fits in the window, centred, so the aspect ratio is kept and pixels
+    /// stay square when the window is resized.
+    fn viewport(&self) -> Rect {
+        let (width, height) = self
+            .canvas
+            .output_size()
+            .expect("Failed to get window size");
+        let scale = (width / gpu::WIDTH).min(height / gpu::HEIGHT).max(1);
+        let (scaled_width, scaled_height) = (gpu::WIDTH * scale, gpu::HEIGHT * scale);
+        Rect::new(
+            (width as i32 - scaled_width as i32) / 2,
+            (height as i32 - scaled_height as i32) / 2,
+            scaled_width,
+            scaled_height,
+        )
+    }
+
+    /// Shows the speed, and whether the emulator is paused, in the window
+    /// title.
+    fn update_title(&mut self) {
+        let speed = match self.speed {
+            Some(speed) => format!("{}x", speed),
+            None => String::from("unlimited"),
+        };
+        let title = if self.paused {
+            format!("Chip8 - {} - paused", speed)
+        } else {
+            format!("Chip8 - {}", speed)
+        };
+        self.canvas
+            .window_mut()
+            .set_title(&title)
+            .expect("Failed to set window title");
+    }
+}
+
+impl VideoSink for Display {
+    /// Draws the phosphor, which follows the VRAM, onto the canvas.
+    fn render(&mut self, _graphics: &Gpu) {
+        let phosphor = &self.phosphor;
+        let background = self.palette.color(false, false);
+        let foreground = self.palette.color(true, false);
+        self.texture
+            .with_lock(None, |buffer: &mut [u8], pitch: usize| {
+                for y in 0..gpu::HEIGHT as usize {
+                    for x in 0..gpu::WIDTH as usize {
+                        let brightness = phosphor.brightness(y * gpu::WIDTH as usize + x);
+                        let color = background.mix(foreground, brightness);
+                        let offset = y * pitch + x * BYTES_PER_PIXEL;
+                        buffer[offset..offset + BYTES_PER_PIXEL]
+                            .copy_from_slice(&[color.0, color.1, color.2]);
+                    }
+                }
+            })
+            .expect("Failed to update texture");
+
+        let viewport = self.viewport();
+        self.canvas
+            .set_draw_color(Color::RGB(background.0, background.1, background.2));
+        self.canvas.clear();
+        self.canvas
+            .copy(&self.texture, None, viewport)
+            .expect("Failed to draw to canvas");
+        self.canvas.present()
+    }
+
+    fn is_fading(&self) -> bool {
+        self.phosphor.is_fading()
+    }
+
+    /// Advances the fading of pixels which have been switched off.
+    fn end_frame(&mut self, graphics: &Gpu) {
+        self.phosphor.update(graphics);
+    }
+
+    fn show_speed(&mut self, speed: Option<f32>) {
+        self.speed = speed;
+        self.update_title();
+    }
+
+    fn show_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        self.update_title();
+    }
+}
+
+/// Represents the keyboard and window events.
+pub struct Input {
+    /// The SDL event pump.
+    events: EventPump,
+}
+
+impl Input {
+    /// Creates a new input instance
+    pub fn new(sdl_context: &sdl2::Sdl) -> Self {
+        let events = sdl_context.event_pump().expect("Failed to get event pump");
+        Input { events }
+    }
+}
+
+impl InputSource for Input {
+    fn poll(&mut self, keypad: &mut Keypad) -> Option<Command> {
+        while let Some(event) = self.events.poll_event() {
+            match event {
+                Event::Quit { .. } => return Some(Command::Quit),
+                Event::Window { .. } => return Some(Command::Redraw),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F10),
+                    repeat: false,
+                    ..
+                } => return Some(Command::ToggleRecording),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F12),
+                    repeat: false,
+                    ..
+                } => return Some(Command::Screenshot),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Tab),
+                    repeat: false,
+                    ..
+                } => return Some(Command::FastForward(true)),
+                Event::KeyUp {
+                    keycode: Some(Keycode::Tab),
+                    ..
+                } => return Some(Command::FastForward(false)),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F5),
+                    ..
+                } => return Some(Command::SpeedDown),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F6),
+                    ..
+                } => return Some(Command::SpeedUp),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F7),
+                    repeat: false,
+                    ..
+                } => return Some(Command::ToggleUnlimitedSpeed),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Space),
+                    repeat: false,
+                    ..
+                } => return Some(Command::TogglePause),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F2),
+                    repeat: false,
+                    ..
+                } => return Some(Command::Reset),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F3),
+                    ..
+                } => return Some(Command::AdvanceFrame),
+                Event::KeyDown {
+                    keycode: Some(key),
+                    repeat: false,
+                    ..
+                } => {
+                    if let Some(index) = map_keycode(keypad, key) {
+                        keypad.key_down(index);
+                    }
+                }
+                Event::KeyUp {
+                    keycode: Some(key), ..
+                } => {
+                    if let Some(index) = map_keycode(keypad, key) {
+                        keypad.key_up(index);
+                    }
+                }
+                _ => (),
+            }
+        }
+        None
+    }
+}
+
+/// Maps the given keyboard-key to a key index
+fn map_keycode(keypad: &Keypad, key: Keycode) -> Option<u8> {
+    // SDL keycodes for printable keys are their ASCII characters
+    std::char::from_u32(key as u32).and_then(|c| keypad.map_char(c))
+}
+
+/// Represents the speaker, which plays a square wave.
+pub struct Audio {
+    /// The playback device.
+    device: AudioDevice<SquareWave>,
+}
+
+impl Audio {
+    /// Creates a new audio instance, paused.
+    pub fn new(sdl_context: &sdl2::Sdl) -> Result<Self, String> {
+        let audio_subsystem = sdl_context.audio()?;
+        let desired = AudioSpecDesired {
+            freq: Some(44_100),
+            channels: Some(1),
+            samples: None,
+        };
+        let device = audio_subsystem.open_playback(None, &desired, |spec| SquareWave {
+            phase_step: TONE_FREQUENCY / spec.freq as f32,
+            phase: 0.0,
+        })?;
+        Ok(Audio { device })
+    }
+}
+
+impl AudioSink for Audio {
+    fn set_tone(&mut self, playing: bool) {
+        if playing {
+            self.device.resume();
+        } else {
+            self.device.pause();
+        }
+    }
+}
+
+/// Generates a square wave at `TONE_FREQUENCY`.
+struct SquareWave {
+    /// How far through a period each sample moves, from 0 to 1.
+    phase_step: f32,
+
+    /// How far through the current period the wave is, from 0 to 1.
+    phase: f32,
+}
+
+impl AudioCallback for SquareWave {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        for sample in out.iter_mut() {
+            *sample = if self.phase < 0.5 {
+                TONE_VOLUME
+            } else {
+                -TONE_VOLUME
+            };
+            self.phase = (self.phase + self.phase_step) % 1.0;
+        }
+    }
+}
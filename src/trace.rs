@@ -0,0 +1,205 @@
+use chip8::cpu::Cpu;
+use chip8::disasm::Instruction;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+
+/// Writes a line to a file for every instruction executed, describing
+/// the CPU just before it runs.
+///
+/// Each line has the cycle count, PC, opcode, mnemonic, V0 to VF, I, SP,
+/// DT and ST, in fixed width columns, so that traces can be diffed
+/// against each other or against other emulators:
+///
+/// ```text
+/// 0000000000 0200 6A02 LD VA, 02     V 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 I 0000 SP 00 DT 00 ST 00
+/// ```
+///
+/// A line reading `RESET` marks each reset of the CPU.
+pub struct Tracer {
+    /// The trace file.
+    out: BufWriter<File>,
+
+    /// The path being written to.
+    path: PathBuf,
+
+    /// Only instructions at these addresses are traced.
+    addresses: RangeInclusive<u16>,
+
+    /// Only instructions in these cycles are traced.
+    cycles: RangeInclusive<u64>,
+}
+
+impl Tracer {
+    /// Starts tracing to the file at the given path, limited to the given
+    /// addresses and cycles.
+    pub fn create(
+        path: &Path,
+        addresses: RangeInclusive<u16>,
+        cycles: RangeInclusive<u64>,
+    ) -> Result<Self, String> {
+        let file = File::create(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        Ok(Tracer {
+            out: BufWriter::new(file),
+            path: path.to_owned(),
+            addresses,
+            cycles,
+        })
+    }
+
+    /// Writes the line for the instruction the CPU is about to execute,
+    /// if it's within the filters.
+    pub fn trace(&mut self, cpu: &Cpu) -> Result<(), String> {
+        if !self.cycles.contains(&cpu.cycles()) || !self.addresses.contains(&cpu.pc()) {
+            return Ok(());
+        }
+
+        write_line(&mut self.out, cpu).map_err(|err| format!("{}: {}", self.path.display(), err))
+    }
+
+    /// Writes a line marking that the CPU was reset, after which the
+    /// cycle count starts again from 0.
+    pub fn reset(&mut self) -> Result<(), String> {
+        writeln!(self.out, "RESET").map_err(|err| format!("{}: {}", self.path.display(), err))
+    }
+
+    /// Flushes the rest of the trace to the file.
+    pub fn finish(mut self) -> Result<PathBuf, String> {
+        self.out
+            .flush()
+            .map_err(|err| format!("{}: {}", self.path.display(), err))?;
+        Ok(self.path)
+    }
+}
+
+/// Writes the line describing the CPU just before it runs its next
+/// instruction.
+fn write_line(out: &mut impl Write, cpu: &Cpu) -> io::Result<()> {
+    let opcode = cpu.opcode();
+    let mnemonic = Instruction::decode(opcode).to_string();
+    write!(
+        out,
+        "{:010} {:04X} {:04X} {:<13} V",
+        cpu.cycles(),
+        cpu.pc(),
+        opcode,
+        mnemonic
+    )?;
+    for v in cpu.v() {
+        write!(out, " {:02X}", v)?;
+    }
+    writeln!(
+        out,
+        " I {:04X} SP {:02X} DT {:02X} ST {:02X}",
+        cpu.i(),
+        cpu.sp(),
+        cpu.dt(),
+        cpu.st()
+    )
+}
+
+/// Parses an inclusive range written as `START-END`, where either end
+/// may be left out to leave that side open. The start can't be after the
+/// end.
+pub fn parse_range<T: PartialOrd>(
+    value: &str,
+    parse: fn(&str) -> Result<T, String>,
+    min: T,
+    max: T,
+) -> Result<RangeInclusive<T>, String> {
+    let mut parts = value.splitn(2, '-');
+    let start = parts.next().unwrap_or("").trim();
+    let end = parts
+        .next()
+        .ok_or_else(|| format!("Invalid range '{}', expected START-END", value))?
+        .trim();
+
+    let start = if start.is_empty() { min } else { parse(start)? };
+    let end = if end.is_empty() { max } else { parse(end)? };
+    if start > end {
+        return Err(format!("Invalid range '{}', START is after END", value));
+    }
+    Ok(start..=end)
+}
+
+/// Parses a hexadecimal address, with or without a `0x` prefix.
+pub fn parse_address(value: &str) -> Result<u16, String> {
+    let digits = value.trim_start_matches("0x").trim_start_matches("0X");
+    u16::from_str_radix(digits, 16).map_err(|err| format!("Invalid address '{}': {}", value, err))
+}
+
+/// Parses a decimal cycle count.
+pub fn parse_cycle(value: &str) -> Result<u64, String> {
+    value
+        .parse()
+        .map_err(|err| format!("Invalid cycle '{}': {}", value, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8::gpu::Gpu;
+    use chip8::keypad::{Keypad, KeypadSetting};
+
+    /// Returns the given number of lines traced from the start of the
+    /// given program, running an instruction after each but the last.
+    fn trace(program: &[u8], lines: u32) -> Vec<String> {
+        let mut cpu: Cpu = Cpu::with_rng(Gpu::new(), Keypad::new(KeypadSetting::Qwerty), || 0);
+        cpu.load_rom(program).unwrap();
+        let mut out = Vec::new();
+        for line in 0..lines {
+            if line > 0 {
+                cpu.execute_cycle();
+            }
+            write_line(&mut out, &cpu).unwrap();
+        }
+        String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn lines_have_fixed_width_columns() {
+        // LD VA, 02; CALL 206; LD I, 123
+        let lines = trace(&[0x6A, 0x02, 0x22, 0x06, 0x00, 0x00, 0xA1, 0x23], 3);
+        assert_eq!(
+            lines,
+            vec![
+                "0000000000 0200 6A02 LD VA, 02     V 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 I 0000 SP 00 DT 00 ST 00",
+                "0000000001 0202 2206 CALL 206      V 00 00 00 00 00 00 00 00 00 00 02 00 00 00 00 00 I 0000 SP 00 DT 00 ST 00",
+                "0000000002 0206 A123 LD I, 123     V 00 00 00 00 00 00 00 00 00 00 02 00 00 00 00 00 I 0000 SP 01 DT 00 ST 00",
+            ]
+        );
+    }
+
+    #[test]
+    fn the_stack_pointer_keeps_its_width() {
+        // CALL 200 until the stack is full
+        let lines = trace(&[0x22, 0x00], 17);
+        assert!(lines.iter().all(|line| line.len() == lines[0].len()));
+        assert!(lines[16].contains(" SP 10 "));
+    }
+
+    #[test]
+    fn parses_ranges() {
+        let range = |value| parse_range(value, parse_address, 0, u16::MAX);
+        assert_eq!(range("200-2FF"), Ok(0x200..=0x2FF));
+        assert_eq!(range("0x200-0x2FF"), Ok(0x200..=0x2FF));
+        assert_eq!(range("300-"), Ok(0x300..=u16::MAX));
+        assert_eq!(range("-300"), Ok(0..=0x300));
+        assert_eq!(range("-"), Ok(0..=u16::MAX));
+        assert_eq!(range("300-300"), Ok(0x300..=0x300));
+        assert!(range("300").is_err());
+        assert!(range("300-200").is_err());
+        assert!(range("300-XYZ").is_err());
+
+        let cycles = |value| parse_range(value, parse_cycle, 0, u64::MAX);
+        assert_eq!(cycles("5000-6000"), Ok(5000..=6000));
+        assert_eq!(cycles("-1000"), Ok(0..=1000));
+        assert!(cycles("6000-5000").is_err());
+        assert!(cycles("-1-2").is_err());
+    }
+}
@@ -0,0 +1,184 @@
+use chip8::frontend::{Command, InputSource, VideoSink};
+use chip8::gpu::{Gpu, Rect};
+use chip8::keypad::Keypad;
+use chip8::palette::{Palette, Rgb};
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
+use crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use std::io::{self, Stdout, Write};
+use std::time::Duration;
+
+/// The number of frames a key is held for after being typed, when the
+/// terminal can't report key releases. Typing repeats keep it held.
+const KEY_HOLD_FRAMES: u32 = 6;
+
+/// The upper half block character. Each character cell draws two pixels
+/// stacked vertically, the top in the foreground colour and the bottom in
+/// the background colour.
+const UPPER_HALF_BLOCK: char = '\u{2580}';
+
+/// Represents the display of a terminal.
+///
+/// The terminal is switched to raw mode and the alternate screen whilst
+/// this exists, and restored when it is dropped.
+pub struct TerminalDisplay {
+    /// The terminal output.
+    stdout: Stdout,
+
+    /// The colours to draw with.
+    palette: Palette,
+
+    /// True if the terminal was asked to report key releases.
+    reports_releases: bool,
+}
+
+impl TerminalDisplay {
+    /// Takes over the terminal.
+    pub fn new(palette: Palette) -> io::Result<Self> {
+        let mut stdout = io::stdout();
+        terminal::enable_raw_mode()?;
+        let reports_releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        if reports_releases {
+            execute!(
+                stdout,
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            )?;
+        }
+        execute!(stdout, EnterAlternateScreen, Hide)?;
+
+        Ok(TerminalDisplay {
+            stdout,
+            palette,
+            reports_releases,
+        })
+    }
+
+    /// Returns the input of the terminal.
+    pub fn input(&self) -> TerminalInput {
+        TerminalInput {
+            reports_releases: self.reports_releases,
+            held_frames: [0; 16],
+        }
+    }
+
+    /// Draws the contents of the VRAM onto the terminal. Only the
+    /// characters covering pixels which have changed are drawn.
+    fn draw(&mut self, graphics: &Gpu) -> io::Result<()> {
+        let region = graphics.dirty_region().unwrap_or(Rect::DISPLAY);
+        let (top, bottom) = (region.y / 2, (region.y + region.height).div_ceil(2));
+        for row in top..bottom {
+            queue!(self.stdout, MoveTo(region.x as u16, row as u16))?;
+            for x in region.x..region.x + region.width {
+                let top = graphics.pixel(x, row * 2);
+                let bottom = graphics.pixel(x, row * 2 + 1);
+                queue!(
+                    self.stdout,
+                    SetForegroundColor(to_color(self.palette.color(top, false))),
+                    SetBackgroundColor(to_color(self.palette.color(bottom, false))),
+                    Print(UPPER_HALF_BLOCK)
+                )?;
+            }
+        }
+        queue!(self.stdout, ResetColor)?;
+        self.stdout.flush()
+    }
+}
+
+impl VideoSink for TerminalDisplay {
+    fn render(&mut self, graphics: &Gpu) {
+        self.draw(graphics).expect("Failed to draw to terminal");
+    }
+}
+
+impl Drop for TerminalDisplay {
+    fn drop(&mut self) {
+        // there's nothing useful to do if restoring the terminal fails
+        if self.reports_releases {
+            let _ = execute!(self.stdout, PopKeyboardEnhancementFlags);
+        }
+        let _ = execute!(self.stdout, ResetColor, Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Represents the keyboard of a terminal.
+pub struct TerminalInput {
+    /// True if the terminal reports key releases. Otherwise, typed keys
+    /// are released after `KEY_HOLD_FRAMES` frames.
+    reports_releases: bool,
+
+    /// The number of frames each key index has left to be held for, when
+    /// the terminal doesn't report key releases.
+    held_frames: [u32; 16],
+}
+
+impl InputSource for TerminalInput {
+    fn poll(&mut self, keypad: &mut Keypad) -> Option<Command> {
+        while event::poll(Duration::from_secs(0)).expect("Failed to read input") {
+            let key = match event::read().expect("Failed to read input") {
+                Event::Key(key) => key,
+                Event::Resize(..) => {
+                    let _ = execute!(io::stdout(), terminal::Clear(terminal::ClearType::All));
+                    return Some(Command::Redraw);
+                }
+                _ => continue,
+            };
+            if is_quit(&key) {
+                return Some(Command::Quit);
+            }
+
+            let index = match key.code {
+                KeyCode::Char(c) => keypad.map_char(c),
+                _ => None,
+            };
+            if let Some(index) = index {
+                match key.kind {
+                    KeyEventKind::Release => keypad.key_up(index),
+                    _ if self.reports_releases => keypad.key_down(index),
+                    _ => {
+                        keypad.key_down(index);
+                        self.held_frames[index as usize] = KEY_HOLD_FRAMES;
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Releases typed keys whose hold has run out, when the terminal
+    /// doesn't report key releases.
+    fn end_frame(&mut self, keypad: &mut Keypad) {
+        if self.reports_releases {
+            return;
+        }
+        for (index, frames) in self.held_frames.iter_mut().enumerate() {
+            if *frames > 0 {
+                *frames -= 1;
+                if *frames == 0 {
+                    keypad.key_up(index as u8);
+                }
+            }
+        }
+    }
+}
+
+/// Returns true if the key asks to quit.
+fn is_quit(key: &KeyEvent) -> bool {
+    key.kind == KeyEventKind::Press
+        && (key.code == KeyCode::Esc
+            || (key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL)))
+}
+
+/// Converts a palette colour to a terminal colour.
+fn to_color(color: Rgb) -> Color {
+    Color::Rgb {
+        r: color.0,
+        g: color.1,
+        b: color.2,
+    }
+}
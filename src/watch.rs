@@ -0,0 +1,175 @@
+use chip8::cpu::{self, RomTooLarge};
+use chip8::frontend::{Command, InputSource};
+use chip8::keypad::Keypad;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often the ROM file is checked for changes.
+const CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Reads the ROM file at the given path, checking that it fits in memory.
+pub fn read_rom(path: &Path) -> Result<Vec<u8>, String> {
+    let data = fs::read(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    if data.len() > cpu::MAX_ROM_SIZE {
+        let err = RomTooLarge { len: data.len() };
+        return Err(format!("{}: {}", path.display(), err));
+    }
+    Ok(data)
+}
+
+/// Wraps an input, and gives a reset command whenever the ROM file it
+/// watches is modified, so that it's loaded again.
+///
+/// The file's modification time and size are polled rather than relying
+/// on file system events, which works the same everywhere, including with
+/// editors which replace the file rather than writing to it. A change is
+/// only reported once the file has stayed the same for a whole poll, so
+/// that a ROM part way through being written by a build isn't loaded.
+pub struct RomWatcher {
+    /// The input being wrapped.
+    input: Box<dyn InputSource>,
+
+    /// The ROM file.
+    path: PathBuf,
+
+    /// How often the file is checked.
+    interval: Duration,
+
+    /// The version of the file last loaded, if known.
+    loaded: Option<Version>,
+
+    /// The changed version of the file seen on the last check, which is
+    /// reported if it's still the same on the next.
+    pending: Option<Version>,
+
+    /// When the file was last checked.
+    checked: Instant,
+}
+
+/// The modification time and size of a file, which change whenever it's
+/// written to.
+type Version = (SystemTime, u64);
+
+impl RomWatcher {
+    /// Starts watching the ROM file at the given path, wrapping the given
+    /// input.
+    pub fn new(input: Box<dyn InputSource>, path: PathBuf) -> Self {
+        let loaded = version(&path);
+        RomWatcher {
+            input,
+            path,
+            interval: CHECK_INTERVAL,
+            loaded,
+            pending: None,
+            checked: Instant::now(),
+        }
+    }
+}
+
+impl InputSource for RomWatcher {
+    fn poll(&mut self, keypad: &mut Keypad) -> Option<Command> {
+        if self.checked.elapsed() >= self.interval {
+            self.checked = Instant::now();
+
+            // a file which is missing part way through being replaced is
+            // picked up once it's back
+            let current = version(&self.path);
+            if current.is_some() && current != self.loaded {
+                if current == self.pending {
+                    self.loaded = current;
+                    self.pending = None;
+                    return Some(Command::Reset);
+                }
+                self.pending = current;
+            } else {
+                self.pending = None;
+            }
+        }
+        self.input.poll(keypad)
+    }
+
+    fn end_frame(&mut self, keypad: &mut Keypad) {
+        self.input.end_frame(keypad);
+    }
+}
+
+/// Returns the version of the file at the given path, or `None` if it
+/// can't be read.
+fn version(path: &Path) -> Option<Version> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8::keypad::KeypadSetting;
+    use std::env;
+
+    /// Input which never presses anything.
+    struct Idle;
+
+    impl InputSource for Idle {
+        fn poll(&mut self, _keypad: &mut Keypad) -> Option<Command> {
+            None
+        }
+    }
+
+    /// Watches a new file in the temporary directory for the given test,
+    /// checking it on every poll.
+    fn watch(name: &str) -> (RomWatcher, PathBuf) {
+        let path = env::temp_dir().join(format!("chip8-{}-{}.ch8", name, std::process::id()));
+        fs::write(&path, [0x12, 0x00]).unwrap();
+        let mut watcher = RomWatcher::new(Box::new(Idle), path.clone());
+        watcher.interval = Duration::from_secs(0);
+        (watcher, path)
+    }
+
+    /// Returns the commands given by polling the watcher the given number
+    /// of times.
+    fn poll(watcher: &mut RomWatcher, times: usize) -> Vec<Option<Command>> {
+        let mut keypad = Keypad::new(KeypadSetting::Qwerty);
+        (0..times).map(|_| watcher.poll(&mut keypad)).collect()
+    }
+
+    #[test]
+    fn a_rewritten_file_is_reported_once_it_settles() {
+        let (mut watcher, path) = watch("rewritten");
+        assert_eq!(poll(&mut watcher, 2), vec![None, None]);
+
+        // the size changes, so this is seen even if the modification time
+        // is too coarse to change
+        fs::write(&path, [0x00, 0xE0, 0x12, 0x00]).unwrap();
+        let commands = poll(&mut watcher, 4);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(commands, vec![None, Some(Command::Reset), None, None]);
+    }
+
+    #[test]
+    fn a_file_still_being_written_is_not_reported() {
+        let (mut watcher, path) = watch("writing");
+        fs::write(&path, [0x00]).unwrap();
+        assert_eq!(poll(&mut watcher, 1), vec![None]);
+        fs::write(&path, [0x00, 0xE0, 0x12]).unwrap();
+        assert_eq!(poll(&mut watcher, 1), vec![None]);
+        fs::write(&path, [0x00, 0xE0, 0x12, 0x02]).unwrap();
+        assert_eq!(poll(&mut watcher, 1), vec![None]);
+
+        let commands = poll(&mut watcher, 2);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(commands, vec![Some(Command::Reset), None]);
+    }
+
+    #[test]
+    fn a_missing_file_is_picked_up_once_it_is_back() {
+        let (mut watcher, path) = watch("missing");
+        fs::remove_file(&path).unwrap();
+        assert_eq!(poll(&mut watcher, 2), vec![None, None]);
+
+        fs::write(&path, [0x00, 0xE0, 0x12, 0x00]).unwrap();
+        let commands = poll(&mut watcher, 3);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(commands, vec![None, Some(Command::Reset), None]);
+    }
+}
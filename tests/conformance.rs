@@ -0,0 +1,215 @@
+//! Runs test ROMs headlessly and compares the display they finish on
+//! against the golden images in `tests/golden`.
+//!
+//! ROMs are either built in, or one of the test programs in `programs`.
+//!
+//! Run with `CHIP8_UPDATE_GOLDEN=1` to record the golden images from the
+//! current behaviour rather than checking against them.
+
+mod programs;
+
+use chip8::cpu::Cpu;
+use chip8::gpu::Gpu;
+use chip8::keypad::{Keypad, KeypadSetting};
+use chip8::quirks::Quirks;
+use chip8::roms;
+use chip8::runner;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// The number of instructions run per frame. The test ROMs only show
+/// their results once they finish, so this is generous.
+const INSTRUCTIONS_PER_FRAME: u32 = 32;
+
+/// The number of frames a scripted key is held for.
+const KEY_HOLD_FRAMES: u32 = 4;
+
+/// Where a test ROM comes from.
+enum Source {
+    /// One of the ROMs in `roms::ROMS`.
+    BuiltIn(&'static str),
+
+    /// One of the test programs, as opcodes.
+    Program(&'static [u16]),
+}
+
+/// A test ROM, along with how to run it.
+struct Case {
+    /// The name of the case, which names its golden image.
+    name: &'static str,
+
+    /// Where the ROM comes from.
+    source: Source,
+
+    /// The interpreter behaviours to follow.
+    quirks: Quirks,
+
+    /// The number of frames to run for.
+    frames: u32,
+
+    /// The keys to press, as the frame to press each on and its index.
+    presses: &'static [(u32, u8)],
+
+    /// The frames to also check the display on before the last, along
+    /// with the suffix of the name of each golden image.
+    snapshots: &'static [(u32, &'static str)],
+}
+
+/// Runs the given case, and compares the display on each snapshot, and
+/// the one it finishes on, against their golden images.
+fn check(case: &Case) {
+    let data = match case.source {
+        Source::BuiltIn(name) => roms::ROMS
+            .iter()
+            .find(|rom| rom.name == name)
+            .expect("Missing built-in ROM")
+            .data
+            .to_vec(),
+        Source::Program(opcodes) => opcodes
+            .iter()
+            .flat_map(|opcode| opcode.to_be_bytes().to_vec())
+            .collect(),
+    };
+
+    // random bytes are fixed so that every run draws the same thing
+    let mut cpu = Cpu::with_rng(Gpu::new(), Keypad::new(KeypadSetting::Qwerty), || 0);
+    cpu.quirks = case.quirks;
+    cpu.load_rom(&data).unwrap();
+    for frame in 0..case.frames {
+        for &(at, key) in case.presses {
+            if frame == at {
+                cpu.keypad.key_down(key);
+            } else if frame == at + KEY_HOLD_FRAMES {
+                cpu.keypad.key_up(key);
+            }
+        }
+        runner::run_frame(&mut cpu, INSTRUCTIONS_PER_FRAME, &mut ());
+
+        for &(at, suffix) in case.snapshots {
+            if frame + 1 == at {
+                compare(&format!("{}-{}", case.name, suffix), &cpu.gpu.to_string());
+            }
+        }
+    }
+    compare(case.name, &cpu.gpu.to_string());
+}
+
+/// Compares the display against the golden image with the given name, or
+/// records it as the golden image if asked to. A missing golden image is
+/// a failure, rather than being recorded.
+fn compare(name: &str, actual: &str) {
+    let golden = manifest_path("tests/golden").join(format!("{}.txt", name));
+    if env::var_os("CHIP8_UPDATE_GOLDEN").is_some() {
+        fs::write(&golden, actual).expect("Failed to write golden image");
+        eprintln!("Recorded {}, check it by eye", golden.display());
+        return;
+    }
+
+    let expected = fs::read_to_string(&golden).unwrap_or_else(|_| {
+        panic!(
+            "Missing golden image {}, run with CHIP8_UPDATE_GOLDEN=1 to record it",
+            golden.display()
+        )
+    });
+    assert!(
+        actual == expected,
+        "{} doesn't match {}\nexpected:{}\nactual:{}",
+        name,
+        golden.display(),
+        expected,
+        actual
+    );
+}
+
+/// Returns the given path relative to the crate root.
+fn manifest_path(path: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(path)
+}
+
+#[test]
+fn bon() {
+    check(&Case {
+        name: "bon",
+        source: Source::BuiltIn("bon"),
+        quirks: Quirks::MODERN,
+        frames: 60,
+        presses: &[],
+        snapshots: &[],
+    });
+}
+
+#[test]
+fn keypad_test() {
+    // each key is highlighted for 16 frames once it's released, and then
+    // cleared again
+    check(&Case {
+        name: "keypad-test",
+        source: Source::BuiltIn("keypad-test"),
+        quirks: Quirks::MODERN,
+        frames: 120,
+        presses: &[(10, 0x1), (40, 0xF), (70, 0xA)],
+        snapshots: &[(20, "1"), (50, "F"), (80, "A")],
+    });
+}
+
+#[test]
+fn opcodes() {
+    check(&Case {
+        name: "opcodes",
+        source: Source::Program(programs::OPCODES),
+        quirks: Quirks::MODERN,
+        frames: 60,
+        presses: &[],
+        snapshots: &[],
+    });
+}
+
+#[test]
+fn flags() {
+    check(&Case {
+        name: "flags",
+        source: Source::Program(programs::FLAGS),
+        quirks: Quirks::MODERN,
+        frames: 60,
+        presses: &[],
+        snapshots: &[],
+    });
+}
+
+#[test]
+fn quirks_modern() {
+    check(&Case {
+        name: "quirks-modern",
+        source: Source::Program(programs::QUIRKS),
+        quirks: Quirks::MODERN,
+        frames: 60,
+        presses: &[],
+        snapshots: &[],
+    });
+}
+
+#[test]
+fn quirks_vip() {
+    check(&Case {
+        name: "quirks-vip",
+        source: Source::Program(programs::QUIRKS),
+        quirks: Quirks::COSMAC_VIP,
+        frames: 60,
+        presses: &[],
+        snapshots: &[],
+    });
+}
+
+#[test]
+fn keypad() {
+    // nothing is shown whilst a key is held, or for keys other than A
+    check(&Case {
+        name: "keypad",
+        source: Source::Program(programs::KEYPAD),
+        quirks: Quirks::MODERN,
+        frames: 90,
+        presses: &[(10, 0x3), (40, 0x5), (55, 0xB), (70, 0xA)],
+        snapshots: &[(12, "held"), (20, "3"), (60, "B"), (72, "A")],
+    });
+}
@@ -0,0 +1,540 @@
+//! Test programs for the conformance tests, written for this crate rather
+//! than taken from a test suite so that they can be shipped with it.
+//!
+//! Each program is listed an opcode at a time, along with its address and
+//! disassembly.
+
+/// Checks the result of every instruction other than drawing, the keypad
+/// and the sound timer, which are covered elsewhere. Each check draws a
+/// tick if it passes or a cross if it fails, 12 to a row.
+pub static OPCODES: &[u16] = &[
+    0x00E0, // 200 CLS
+    0x6401, // 202 LD V4, 01
+    0x6501, // 204 LD V5, 01
+    // 1 to 8: skips
+    0x6205, // 206 LD V2, 05
+    0x6305, // 208 LD V3, 05
+    0x6000, // 20A LD V0, 00
+    0x6100, // 20C LD V1, 00
+    0x3205, // 20E SE V2, 05
+    0x6001, // 210 LD V0, 01
+    0x2392, // 212 CALL 392
+    0x6000, // 214 LD V0, 00
+    0x6101, // 216 LD V1, 01
+    0x3206, // 218 SE V2, 06
+    0x6001, // 21A LD V0, 01
+    0x2392, // 21C CALL 392
+    0x6000, // 21E LD V0, 00
+    0x6100, // 220 LD V1, 00
+    0x4206, // 222 SNE V2, 06
+    0x6001, // 224 LD V0, 01
+    0x2392, // 226 CALL 392
+    0x6000, // 228 LD V0, 00
+    0x6101, // 22A LD V1, 01
+    0x4205, // 22C SNE V2, 05
+    0x6001, // 22E LD V0, 01
+    0x2392, // 230 CALL 392
+    0x6000, // 232 LD V0, 00
+    0x6100, // 234 LD V1, 00
+    0x5230, // 236 SE V2, V3
+    0x6001, // 238 LD V0, 01
+    0x2392, // 23A CALL 392
+    0x6306, // 23C LD V3, 06
+    0x6000, // 23E LD V0, 00
+    0x6101, // 240 LD V1, 01
+    0x5230, // 242 SE V2, V3
+    0x6001, // 244 LD V0, 01
+    0x2392, // 246 CALL 392
+    0x6000, // 248 LD V0, 00
+    0x6100, // 24A LD V1, 00
+    0x9230, // 24C SNE V2, V3
+    0x6001, // 24E LD V0, 01
+    0x2392, // 250 CALL 392
+    0x6305, // 252 LD V3, 05
+    0x6000, // 254 LD V0, 00
+    0x6101, // 256 LD V1, 01
+    0x9230, // 258 SNE V2, V3
+    0x6001, // 25A LD V0, 01
+    0x2392, // 25C CALL 392
+    // 9 to 21: loads and arithmetic, shifting with Vx equal to Vy so that the
+    // shift quirk doesn't matter
+    0x6042, // 25E LD V0, 42
+    0x6142, // 260 LD V1, 42
+    0x2392, // 262 CALL 392
+    0x62FF, // 264 LD V2, FF
+    0x7202, // 266 ADD V2, 02
+    0x8020, // 268 LD V0, V2
+    0x6101, // 26A LD V1, 01
+    0x2392, // 26C CALL 392
+    0x6337, // 26E LD V3, 37
+    0x8230, // 270 LD V2, V3
+    0x8020, // 272 LD V0, V2
+    0x6137, // 274 LD V1, 37
+    0x2392, // 276 CALL 392
+    0x620F, // 278 LD V2, 0F
+    0x63F0, // 27A LD V3, F0
+    0x8231, // 27C OR V2, V3
+    0x8020, // 27E LD V0, V2
+    0x61FF, // 280 LD V1, FF
+    0x2392, // 282 CALL 392
+    0x623C, // 284 LD V2, 3C
+    0x630F, // 286 LD V3, 0F
+    0x8232, // 288 AND V2, V3
+    0x8020, // 28A LD V0, V2
+    0x610C, // 28C LD V1, 0C
+    0x2392, // 28E CALL 392
+    0x623C, // 290 LD V2, 3C
+    0x630F, // 292 LD V3, 0F
+    0x8233, // 294 XOR V2, V3
+    0x8020, // 296 LD V0, V2
+    0x6133, // 298 LD V1, 33
+    0x2392, // 29A CALL 392
+    0x6210, // 29C LD V2, 10
+    0x6320, // 29E LD V3, 20
+    0x8234, // 2A0 ADD V2, V3
+    0x8020, // 2A2 LD V0, V2
+    0x6130, // 2A4 LD V1, 30
+    0x2392, // 2A6 CALL 392
+    0x62F0, // 2A8 LD V2, F0
+    0x6320, // 2AA LD V3, 20
+    0x8234, // 2AC ADD V2, V3
+    0x8020, // 2AE LD V0, V2
+    0x6110, // 2B0 LD V1, 10
+    0x2392, // 2B2 CALL 392
+    0x6230, // 2B4 LD V2, 30
+    0x6310, // 2B6 LD V3, 10
+    0x8235, // 2B8 SUB V2, V3
+    0x8020, // 2BA LD V0, V2
+    0x6120, // 2BC LD V1, 20
+    0x2392, // 2BE CALL 392
+    0x6210, // 2C0 LD V2, 10
+    0x6330, // 2C2 LD V3, 30
+    0x8235, // 2C4 SUB V2, V3
+    0x8020, // 2C6 LD V0, V2
+    0x61E0, // 2C8 LD V1, E0
+    0x2392, // 2CA CALL 392
+    0x6210, // 2CC LD V2, 10
+    0x6330, // 2CE LD V3, 30
+    0x8237, // 2D0 SUBN V2, V3
+    0x8020, // 2D2 LD V0, V2
+    0x6120, // 2D4 LD V1, 20
+    0x2392, // 2D6 CALL 392
+    0x6281, // 2D8 LD V2, 81
+    0x6381, // 2DA LD V3, 81
+    0x8236, // 2DC SHR V2, V3
+    0x8020, // 2DE LD V0, V2
+    0x6140, // 2E0 LD V1, 40
+    0x2392, // 2E2 CALL 392
+    0x6281, // 2E4 LD V2, 81
+    0x6381, // 2E6 LD V3, 81
+    0x823E, // 2E8 SHL V2, V3
+    0x8020, // 2EA LD V0, V2
+    0x6102, // 2EC LD V1, 02
+    0x2392, // 2EE CALL 392
+    // 22 to 29: memory
+    0xA3AE, // 2F0 LD I, 3AE
+    0x6201, // 2F2 LD V2, 01
+    0xF21E, // 2F4 ADD I, V2
+    0xF065, // 2F6 LD V0, [I]
+    0x61CD, // 2F8 LD V1, CD
+    0x2392, // 2FA CALL 392
+    0x6011, // 2FC LD V0, 11
+    0x6122, // 2FE LD V1, 22
+    0x6233, // 300 LD V2, 33
+    0xA3B0, // 302 LD I, 3B0
+    0xF255, // 304 LD [I], V2
+    0x6000, // 306 LD V0, 00
+    0x6100, // 308 LD V1, 00
+    0x6200, // 30A LD V2, 00
+    0xA3B0, // 30C LD I, 3B0
+    0xF265, // 30E LD V2, [I]
+    0x8610, // 310 LD V6, V1
+    0x8720, // 312 LD V7, V2
+    0x6111, // 314 LD V1, 11
+    0x2392, // 316 CALL 392
+    0x8060, // 318 LD V0, V6
+    0x6122, // 31A LD V1, 22
+    0x2392, // 31C CALL 392
+    0x8070, // 31E LD V0, V7
+    0x6133, // 320 LD V1, 33
+    0x2392, // 322 CALL 392
+    0xA3B0, // 324 LD I, 3B0
+    0xF365, // 326 LD V3, [I]
+    0x8030, // 328 LD V0, V3
+    0x61EE, // 32A LD V1, EE
+    0x2392, // 32C CALL 392
+    0x6289, // 32E LD V2, 89
+    0xA3B0, // 330 LD I, 3B0
+    0xF233, // 332 LD B, V2
+    0xF265, // 334 LD V2, [I]
+    0x8610, // 336 LD V6, V1
+    0x8720, // 338 LD V7, V2
+    0x6101, // 33A LD V1, 01
+    0x2392, // 33C CALL 392
+    0x8060, // 33E LD V0, V6
+    0x6103, // 340 LD V1, 03
+    0x2392, // 342 CALL 392
+    0x8070, // 344 LD V0, V7
+    0x6107, // 346 LD V1, 07
+    0x2392, // 348 CALL 392
+    // 30 to 33: the font, subroutines, jumps and random numbers, which the
+    // tests fix at 0
+    0x6201, // 34A LD V2, 01
+    0xF229, // 34C LD F, V2
+    0xF065, // 34E LD V0, [I]
+    0x6120, // 350 LD V1, 20
+    0x2392, // 352 CALL 392
+    0x6000, // 354 LD V0, 00
+    0x238E, // 356 CALL 38E
+    0x6199, // 358 LD V1, 99
+    0x2392, // 35A CALL 392
+    0x6004, // 35C LD V0, 04
+    0xB360, // 35E JP V0, 360
+    // table:
+    0x6201, // 360 LD V2, 01
+    0x1366, // 362 JP 366
+    0x6202, // 364 LD V2, 02
+    // done:
+    0x8020, // 366 LD V0, V2
+    0x6102, // 368 LD V1, 02
+    0x2392, // 36A CALL 392
+    0x60FF, // 36C LD V0, FF
+    0xC00F, // 36E RND V0, 0F
+    0x6100, // 370 LD V1, 00
+    0x2392, // 372 CALL 392
+    // 34 and 35: the delay timer counts down to 0, and reads back what it was
+    // set to within a frame
+    0x6203, // 374 LD V2, 03
+    0xF215, // 376 LD DT, V2
+    // wait:
+    0xF007, // 378 LD V0, DT
+    0x3000, // 37A SE V0, 00
+    0x1378, // 37C JP 378
+    0x6100, // 37E LD V1, 00
+    0x2392, // 380 CALL 392
+    0x6220, // 382 LD V2, 20
+    0xF215, // 384 LD DT, V2
+    0xF007, // 386 LD V0, DT
+    0x6120, // 388 LD V1, 20
+    0x2392, // 38A CALL 392
+    // end:
+    0x138C, // 38C JP 38C
+    // sub:
+    0x6099, // 38E LD V0, 99
+    0x00EE, // 390 RET
+    // draws a tick at (V4, V5) if V0 equals V1, or a cross if not, and moves
+    // along to the next mark, 12 to a row
+    // check:
+    0xA3AA, // 392 LD I, 3AA
+    0x9010, // 394 SNE V0, V1
+    0xA3A6, // 396 LD I, 3A6
+    0xD454, // 398 DRW V4, V5, 4
+    0x7405, // 39A ADD V4, 05
+    0x343D, // 39C SE V4, 3D
+    0x00EE, // 39E RET
+    0x6401, // 3A0 LD V4, 01
+    0x7505, // 3A2 ADD V5, 05
+    0x00EE, // 3A4 RET
+    // tick:
+    0x1020, // 3A6 DW 1020
+    0xA040, // 3A8 DW A040
+    // cross:
+    0x9060, // 3AA DW 9060
+    0x6090, // 3AC DW 6090
+    // data:
+    0xABCD, // 3AE DW ABCD
+    // scratch:
+    0x0000, // 3B0 DW 0000
+    0x00EE, // 3B2 DW 00EE
+];
+
+/// Checks VF after every instruction which sets it, including when it's
+/// also an operand, along with some which mustn't change it. Each check
+/// draws a tick if it passes or a cross if it fails, 12 to a row.
+pub static FLAGS: &[u16] = &[
+    0x00E0, // 200 CLS
+    0x6401, // 202 LD V4, 01
+    0x6501, // 204 LD V5, 01
+    // 1 to 13: VF after arithmetic, shifting with Vx equal to Vy so that the
+    // shift quirk doesn't matter
+    0x6210, // 206 LD V2, 10
+    0x6320, // 208 LD V3, 20
+    0x8234, // 20A ADD V2, V3
+    0x80F0, // 20C LD V0, VF
+    0x6100, // 20E LD V1, 00
+    0x2326, // 210 CALL 326
+    0x62FF, // 212 LD V2, FF
+    0x6301, // 214 LD V3, 01
+    0x8234, // 216 ADD V2, V3
+    0x80F0, // 218 LD V0, VF
+    0x6101, // 21A LD V1, 01
+    0x2326, // 21C CALL 326
+    0x8020, // 21E LD V0, V2
+    0x6100, // 220 LD V1, 00
+    0x2326, // 222 CALL 326
+    0x6230, // 224 LD V2, 30
+    0x6310, // 226 LD V3, 10
+    0x8235, // 228 SUB V2, V3
+    0x80F0, // 22A LD V0, VF
+    0x6101, // 22C LD V1, 01
+    0x2326, // 22E CALL 326
+    0x6210, // 230 LD V2, 10
+    0x6330, // 232 LD V3, 30
+    0x8235, // 234 SUB V2, V3
+    0x80F0, // 236 LD V0, VF
+    0x6100, // 238 LD V1, 00
+    0x2326, // 23A CALL 326
+    0x6242, // 23C LD V2, 42
+    0x6342, // 23E LD V3, 42
+    0x8235, // 240 SUB V2, V3
+    0x80F0, // 242 LD V0, VF
+    0x6101, // 244 LD V1, 01
+    0x2326, // 246 CALL 326
+    0x6210, // 248 LD V2, 10
+    0x6330, // 24A LD V3, 30
+    0x8237, // 24C SUBN V2, V3
+    0x80F0, // 24E LD V0, VF
+    0x6101, // 250 LD V1, 01
+    0x2326, // 252 CALL 326
+    0x6230, // 254 LD V2, 30
+    0x6310, // 256 LD V3, 10
+    0x8237, // 258 SUBN V2, V3
+    0x80F0, // 25A LD V0, VF
+    0x6100, // 25C LD V1, 00
+    0x2326, // 25E CALL 326
+    0x6242, // 260 LD V2, 42
+    0x6342, // 262 LD V3, 42
+    0x8237, // 264 SUBN V2, V3
+    0x80F0, // 266 LD V0, VF
+    0x6101, // 268 LD V1, 01
+    0x2326, // 26A CALL 326
+    0x6281, // 26C LD V2, 81
+    0x6381, // 26E LD V3, 81
+    0x8236, // 270 SHR V2, V3
+    0x80F0, // 272 LD V0, VF
+    0x6101, // 274 LD V1, 01
+    0x2326, // 276 CALL 326
+    0x6280, // 278 LD V2, 80
+    0x6380, // 27A LD V3, 80
+    0x8236, // 27C SHR V2, V3
+    0x80F0, // 27E LD V0, VF
+    0x6100, // 280 LD V1, 00
+    0x2326, // 282 CALL 326
+    0x6281, // 284 LD V2, 81
+    0x6381, // 286 LD V3, 81
+    0x823E, // 288 SHL V2, V3
+    0x80F0, // 28A LD V0, VF
+    0x6101, // 28C LD V1, 01
+    0x2326, // 28E CALL 326
+    0x6201, // 290 LD V2, 01
+    0x6301, // 292 LD V3, 01
+    0x823E, // 294 SHL V2, V3
+    0x80F0, // 296 LD V0, VF
+    0x6100, // 298 LD V1, 00
+    0x2326, // 29A CALL 326
+    // 14 to 18: the flag wins when VF is the destination
+    0x6FFF, // 29C LD VF, FF
+    0x6301, // 29E LD V3, 01
+    0x8F34, // 2A0 ADD VF, V3
+    0x80F0, // 2A2 LD V0, VF
+    0x6101, // 2A4 LD V1, 01
+    0x2326, // 2A6 CALL 326
+    0x6F10, // 2A8 LD VF, 10
+    0x6320, // 2AA LD V3, 20
+    0x8F35, // 2AC SUB VF, V3
+    0x80F0, // 2AE LD V0, VF
+    0x6100, // 2B0 LD V1, 00
+    0x2326, // 2B2 CALL 326
+    0x6F10, // 2B4 LD VF, 10
+    0x6320, // 2B6 LD V3, 20
+    0x8F37, // 2B8 SUBN VF, V3
+    0x80F0, // 2BA LD V0, VF
+    0x6101, // 2BC LD V1, 01
+    0x2326, // 2BE CALL 326
+    0x6F03, // 2C0 LD VF, 03
+    0x8FF6, // 2C2 SHR VF, VF
+    0x80F0, // 2C4 LD V0, VF
+    0x6101, // 2C6 LD V1, 01
+    0x2326, // 2C8 CALL 326
+    0x6F40, // 2CA LD VF, 40
+    0x8FFE, // 2CC SHL VF, VF
+    0x80F0, // 2CE LD V0, VF
+    0x6100, // 2D0 LD V1, 00
+    0x2326, // 2D2 CALL 326
+    // 19 and 20: VF as an operand is read before the flag is written
+    0x6210, // 2D4 LD V2, 10
+    0x6FF0, // 2D6 LD VF, F0
+    0x82F4, // 2D8 ADD V2, VF
+    0x80F0, // 2DA LD V0, VF
+    0x6101, // 2DC LD V1, 01
+    0x2326, // 2DE CALL 326
+    0x8020, // 2E0 LD V0, V2
+    0x6100, // 2E2 LD V1, 00
+    0x2326, // 2E4 CALL 326
+    // 21 to 23: adding to a register leaves VF alone, and adding to I only
+    // sets it if I passes FFF
+    0x6F07, // 2E6 LD VF, 07
+    0x62FF, // 2E8 LD V2, FF
+    0x7202, // 2EA ADD V2, 02
+    0x80F0, // 2EC LD V0, VF
+    0x6107, // 2EE LD V1, 07
+    0x2326, // 2F0 CALL 326
+    0x6F07, // 2F2 LD VF, 07
+    0xA33A, // 2F4 LD I, 33A
+    0x62FF, // 2F6 LD V2, FF
+    0xF21E, // 2F8 ADD I, V2
+    0x80F0, // 2FA LD V0, VF
+    0x6100, // 2FC LD V1, 00
+    0x2326, // 2FE CALL 326
+    0xAFFF, // 300 LD I, FFF
+    0x6201, // 302 LD V2, 01
+    0xF21E, // 304 ADD I, V2
+    0x80F0, // 306 LD V0, VF
+    0x6101, // 308 LD V1, 01
+    0x2326, // 30A CALL 326
+    // 24 and 25: drawing sets VF on collisions, which erase the sprite again
+    0x6238, // 30C LD V2, 38
+    0x631A, // 30E LD V3, 1A
+    0xA33A, // 310 LD I, 33A
+    0xD234, // 312 DRW V2, V3, 4
+    0x80F0, // 314 LD V0, VF
+    0x6100, // 316 LD V1, 00
+    0x2326, // 318 CALL 326
+    0xA33A, // 31A LD I, 33A
+    0xD234, // 31C DRW V2, V3, 4
+    0x80F0, // 31E LD V0, VF
+    0x6101, // 320 LD V1, 01
+    0x2326, // 322 CALL 326
+    // end:
+    0x1324, // 324 JP 324
+    // draws a tick at (V4, V5) if V0 equals V1, or a cross if not, and moves
+    // along to the next mark, 12 to a row
+    // check:
+    0xA33E, // 326 LD I, 33E
+    0x9010, // 328 SNE V0, V1
+    0xA33A, // 32A LD I, 33A
+    0xD454, // 32C DRW V4, V5, 4
+    0x7405, // 32E ADD V4, 05
+    0x343D, // 330 SE V4, 3D
+    0x00EE, // 332 RET
+    0x6401, // 334 LD V4, 01
+    0x7505, // 336 ADD V5, 05
+    0x00EE, // 338 RET
+    // tick:
+    0x1020, // 33A DW 1020
+    0xA040, // 33C DW A040
+    // cross:
+    0x9060, // 33E DW 9060
+    0x6090, // 340 DW 6090
+];
+
+/// Shows how the interpreter behaves where the quirks differ, as a row of
+/// digits followed by blocks drawn across the edges of the display.
+pub static QUIRKS: &[u16] = &[
+    0x00E0, // 200 CLS
+    0x6402, // 202 LD V4, 02
+    0x6502, // 204 LD V5, 02
+    // 1 to 3: VF after OR, AND and XOR, which is 0 if they reset it or 5 if not
+    0x6F05, // 206 LD VF, 05
+    0x6201, // 208 LD V2, 01
+    0x6302, // 20A LD V3, 02
+    0x8231, // 20C OR V2, V3
+    0x80F0, // 20E LD V0, VF
+    0x2266, // 210 CALL 266
+    0x6F05, // 212 LD VF, 05
+    0x8232, // 214 AND V2, V3
+    0x80F0, // 216 LD V0, VF
+    0x2266, // 218 CALL 266
+    0x6F05, // 21A LD VF, 05
+    0x8233, // 21C XOR V2, V3
+    0x80F0, // 21E LD V0, VF
+    0x2266, // 220 CALL 266
+    // 4 and 5: shifting 1, which gives 0 and 2, or shifting Vy = 4 instead,
+    // which gives 2 and 8
+    0x6201, // 222 LD V2, 01
+    0x6304, // 224 LD V3, 04
+    0x8236, // 226 SHR V2, V3
+    0x8020, // 228 LD V0, V2
+    0x2266, // 22A CALL 266
+    0x6201, // 22C LD V2, 01
+    0x6304, // 22E LD V3, 04
+    0x823E, // 230 SHL V2, V3
+    0x8020, // 232 LD V0, V2
+    0x2266, // 234 CALL 266
+    // 6: the number of frames 4 draws take, starting at the start of a frame
+    0x6201, // 236 LD V2, 01
+    0xF215, // 238 LD DT, V2
+    // sync:
+    0xF007, // 23A LD V0, DT
+    0x3000, // 23C SE V0, 00
+    0x123A, // 23E JP 23A
+    0x620A, // 240 LD V2, 0A
+    0xF215, // 242 LD DT, V2
+    0xA26E, // 244 LD I, 26E
+    0xD661, // 246 DRW V6, V6, 1
+    0xD661, // 248 DRW V6, V6, 1
+    0xD661, // 24A DRW V6, V6, 1
+    0xD661, // 24C DRW V6, V6, 1
+    0xF007, // 24E LD V0, DT
+    0x610A, // 250 LD V1, 0A
+    0x8017, // 252 SUBN V0, V1
+    0x2266, // 254 CALL 266
+    // then blocks across the right and bottom edges, which either wrap around
+    // to the left and top or are clipped
+    0xA270, // 256 LD I, 270
+    0x623C, // 258 LD V2, 3C
+    0x6314, // 25A LD V3, 14
+    0xD234, // 25C DRW V2, V3, 4
+    0x621C, // 25E LD V2, 1C
+    0x631E, // 260 LD V3, 1E
+    0xD234, // 262 DRW V2, V3, 4
+    // end:
+    0x1264, // 264 JP 264
+    // draws the digit in V0 at (V4, V5), and moves along to the next one
+    // show:
+    0xF029, // 266 LD F, V0
+    0xD455, // 268 DRW V4, V5, 5
+    0x7406, // 26A ADD V4, 06
+    0x00EE, // 26C RET
+    // blank:
+    0x0000, // 26E DW 0000
+    // block:
+    0xFFFF, // 270 DW FFFF
+    0xFFFF, // 272 DW FFFF
+];
+
+/// Shows the first two keys pressed and released, followed by A once it's
+/// held and a tick once it's released again.
+pub static KEYPAD: &[u16] = &[
+    0x00E0, // 200 CLS
+    0x6402, // 202 LD V4, 02
+    0x6502, // 204 LD V5, 02
+    // the first two keys pressed and released
+    0xF00A, // 206 LD V0, K
+    0x2222, // 208 CALL 222
+    0xF00A, // 20A LD V0, K
+    0x2222, // 20C CALL 222
+    // A once it's held, and then a tick once it's released
+    0x660A, // 20E LD V6, 0A
+    // hold:
+    0xE69E, // 210 SKP V6
+    0x1210, // 212 JP 210
+    0x8060, // 214 LD V0, V6
+    0x2222, // 216 CALL 222
+    // release:
+    0xE6A1, // 218 SKNP V6
+    0x1218, // 21A JP 218
+    0xA22A, // 21C LD I, 22A
+    0xD454, // 21E DRW V4, V5, 4
+    // end:
+    0x1220, // 220 JP 220
+    // draws the digit in V0 at (V4, V5), and moves along to the next one
+    // show:
+    0xF029, // 222 LD F, V0
+    0xD455, // 224 DRW V4, V5, 5
+    0x7406, // 226 ADD V4, 06
+    0x00EE, // 228 RET
+    // tick:
+    0x1020, // 22A DW 1020
+    0xA040, // 22C DW A040
+];